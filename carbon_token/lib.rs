@@ -1,9 +1,128 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+/// Maximum number of entries kept in the supply history.
+const SUPPLY_HISTORY_LIMIT: usize = 64;
+
 #[ink::contract]
 mod carbon_token {
+    use ink::prelude::string::String;
+    use ink::prelude::vec::Vec;
     use ink::storage::Mapping;
 
+    /// Identifier of a retirement certificate.
+    pub type CertificateId = u128;
+
+    /// Identifier of a balance snapshot.
+    pub type SnapshotId = u32;
+
+    /// `(snapshot_id, value)` pairs of a value recorded before its first change following each snapshot.
+    pub type SnapshotHistory = Vec<(SnapshotId, Balance)>;
+
+    /// Record of a carbon credit retirement.
+    #[derive(scale::Decode, scale::Encode, Debug, PartialEq, Eq, Clone)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct RetirementRecord {
+        /// Account whose tokens were retired.
+        retiree: AccountId,
+        /// Beneficiary of the offset.
+        beneficiary: String,
+        /// Reason of the offset, e.g. the emission it compensates.
+        reason: String,
+        /// Amount of tokens retired.
+        amount: Balance,
+        /// Block number of the retirement.
+        block: BlockNumber,
+        /// Block timestamp of the retirement.
+        timestamp: Timestamp,
+    }
+
+    /// Offset project underlying the token.
+    #[derive(scale::Decode, scale::Encode, Debug, Default, PartialEq, Eq, Clone)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ProjectMetadata {
+        /// Registry listing the project, e.g. `Verra`.
+        pub registry: String,
+        /// Identifier of the project in the registry.
+        pub project_id: String,
+        /// Methodology used to quantify the offsets.
+        pub methodology: String,
+        /// Country where the project is located.
+        pub country: String,
+    }
+
+    /// Optional features of a token, combined at construction by `with_config`.
+    #[derive(scale::Decode, scale::Encode, Debug, PartialEq, Eq, Clone)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct TokenConfig {
+        /// Name of the token.
+        pub name: String,
+        /// Symbol of the token.
+        pub symbol: String,
+        /// Number of decimals used to display balances.
+        pub decimals: u8,
+        /// Maximum total supply, `None` if uncapped.
+        pub cap: Option<Balance>,
+        /// Timestamp after which the token can only be burned, `None` if it never expires.
+        pub expiry_timestamp: Option<Timestamp>,
+        /// Vintage year of the credits, if any.
+        pub vintage: Option<u16>,
+        /// Offset project underlying the token.
+        pub project_metadata: ProjectMetadata,
+    }
+
+    impl Default for TokenConfig {
+        fn default() -> Self {
+            Self {
+                name: String::new(),
+                symbol: String::new(),
+                decimals: 18,
+                cap: None,
+                expiry_timestamp: None,
+                vintage: None,
+                project_metadata: ProjectMetadata::default(),
+            }
+        }
+    }
+
+    /// Tokens unlocking linearly to a beneficiary.
+    #[derive(scale::Decode, scale::Encode, Debug, PartialEq, Eq, Clone)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct VestingSchedule {
+        /// Total amount of tokens vesting.
+        pub total: Balance,
+        /// Amount of vested tokens already claimed.
+        pub claimed: Balance,
+        /// Timestamp at which vesting starts.
+        pub start: Timestamp,
+        /// Time over which the tokens vest, in milliseconds.
+        pub duration: Timestamp,
+    }
+
+    /// Aggregated health of the token.
+    #[derive(scale::Decode, scale::Encode, Debug, PartialEq, Eq, Clone)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct TokenStats {
+        /// Total token supply.
+        pub total_supply: Balance,
+        /// Total amount of tokens ever burned.
+        pub total_burned: Balance,
+        /// Total amount of tokens retired as carbon offsets.
+        pub total_retired: Balance,
+        /// Number of accounts holding a non-zero balance.
+        pub holder_count: u32,
+        /// Whether the token is paused.
+        pub paused: bool,
+    }
+
     /// Create storage for a simple ERC-20 contract.
     #[ink(storage)]
     pub struct CarbonToken {
@@ -13,6 +132,83 @@ mod carbon_token {
         balances: Mapping<AccountId, Balance>,
         /// Approval spender on behalf of the message's sender.
         allowances: Mapping<(AccountId, AccountId), Balance>,
+        /// Account allowed to administer the token.
+        owner: AccountId,
+        /// Mapping from account to its frozen status.
+        frozen: Mapping<AccountId, bool>,
+        /// Bounded history of `(block_number, total_supply)` after each supply change.
+        supply_history: Vec<(BlockNumber, Balance)>,
+        /// Total amount of tokens retired as carbon offsets.
+        total_retired: Balance,
+        /// Total amount of tokens ever minted.
+        total_minted: Balance,
+        /// Total amount of tokens ever burned.
+        total_burned: Balance,
+        /// Tax charged on transfers, in basis points of the transferred value.
+        transfer_tax_bps: u16,
+        /// Account credited with the transfer tax.
+        tax_recipient: AccountId,
+        /// Mapping from account to its transfer tax exemption.
+        tax_exempt: Mapping<AccountId, bool>,
+        /// Minimum value of a non-zero transfer, zero disables the check.
+        min_transfer_amount: Balance,
+        /// Name of the token.
+        name: String,
+        /// Symbol of the token.
+        symbol: String,
+        /// Number of decimals of the token.
+        decimals: u8,
+        /// Compressed ECDSA public key allowed to sign mint vouchers, stored as bytes since
+        /// `[u8; 33]` has no storage layout.
+        voucher_authority: Option<Vec<u8>>,
+        /// Voucher nonces that were already redeemed.
+        used_voucher_nonces: Mapping<u64, ()>,
+        /// Mapping from account to the nonce expected in its next permit.
+        nonces: Mapping<AccountId, u64>,
+        /// Mapping from certificate id to retirement record.
+        retirements: Mapping<CertificateId, RetirementRecord>,
+        /// Id of the next retirement certificate.
+        next_certificate_id: CertificateId,
+        /// Mapping from account to whether tokens sent to it are retired on receipt.
+        auto_retire_sinks: Mapping<AccountId, bool>,
+        /// Timestamp after which the token can only be burned.
+        expiry_timestamp: Option<Timestamp>,
+        /// Vintage year of the carbon credits represented by the token.
+        vintage: Option<u16>,
+        /// Offset project underlying the token.
+        project_metadata: ProjectMetadata,
+        /// Maximum amount minted, burned and retired within a single block, zero disables the check.
+        max_supply_delta_per_block: Balance,
+        /// Block of the tracked supply changes.
+        supply_delta_block: BlockNumber,
+        /// Amount minted and burned within `supply_delta_block`.
+        supply_delta_in_block: Balance,
+        /// Number of accounts holding a non-zero balance.
+        holder_count: u32,
+        /// Whether the token is paused.
+        paused: bool,
+        /// Mapping from beneficiary to the total amount retired on its behalf.
+        retired_by_beneficiary: Mapping<String, Balance>,
+        /// Mapping from account to whether it may mint.
+        minters: Mapping<AccountId, bool>,
+        /// Maximum total supply, `None` if uncapped.
+        cap: Option<Balance>,
+        /// Whether transfers require a whitelisted party.
+        transfer_restricted: bool,
+        /// Mapping from account to whether it may transfer while transfers are restricted.
+        transfer_whitelist: Mapping<AccountId, bool>,
+        /// Id of the latest snapshot, zero before the first snapshot.
+        current_snapshot_id: SnapshotId,
+        /// Mapping from account to its `(snapshot_id, balance)` pairs, recorded before the
+        /// first balance change following each snapshot.
+        account_snapshots: Mapping<AccountId, SnapshotHistory>,
+        /// Mapping from index to the `(snapshot_id, total_supply)` pairs, recorded before the first
+        /// supply change following each snapshot.
+        total_supply_snapshots: Mapping<u32, (SnapshotId, Balance)>,
+        /// Number of recorded total supply snapshots.
+        total_supply_snapshot_count: u32,
+        /// Mapping from beneficiary to its vesting schedule, whose tokens are held by the contract.
+        vesting: Mapping<AccountId, VestingSchedule>,
     }
 
     #[ink(event)]
@@ -33,21 +229,100 @@ mod carbon_token {
         value: Balance,
     }
 
+    /// Event emitted when tokens are minted by the `minter` to `to`.
     #[ink(event)]
     pub struct Mint {
         #[ink(topic)]
         minter: AccountId,
         #[ink(topic)]
+        to: AccountId,
         amount: Balance,
     }
 
+    /// Event emitted when tokens of the `burner` are burned.
     #[ink(event)]
     pub struct Burn {
         #[ink(topic)]
-        from: AccountId,
+        burner: AccountId,
+        amount: Balance,
+    }
+
+    /// Event emitted when the ownership of the token changes.
+    #[ink(event)]
+    pub struct OwnershipTransferred {
         #[ink(topic)]
-        to: AccountId,
+        previous: AccountId,
+        #[ink(topic)]
+        new: AccountId,
+    }
+
+    /// Event emitted when the token is paused.
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        by: AccountId,
+    }
+
+    /// Event emitted when the token is unpaused.
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        by: AccountId,
+    }
+
+    /// Event emitted when an account is frozen.
+    #[ink(event)]
+    pub struct AccountFrozen {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    /// Event emitted when an account is unfrozen.
+    #[ink(event)]
+    pub struct AccountUnfrozen {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    /// Event emitted when an account is allowed to mint.
+    #[ink(event)]
+    pub struct MinterGranted {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    /// Event emitted when an account is no longer allowed to mint.
+    #[ink(event)]
+    pub struct MinterRevoked {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    /// Event emitted when tokens start vesting to a `beneficiary`.
+    #[ink(event)]
+    pub struct VestingCreated {
+        #[ink(topic)]
+        beneficiary: AccountId,
+        total: Balance,
+        start: Timestamp,
+        duration: Timestamp,
+    }
+
+    /// Event emitted when a balance snapshot is taken.
+    #[ink(event)]
+    pub struct Snapshot {
+        #[ink(topic)]
+        id: SnapshotId,
+    }
+
+    /// Event emitted when tokens are retired as carbon offsets.
+    #[ink(event)]
+    pub struct Retire {
+        #[ink(topic)]
+        id: CertificateId,
         #[ink(topic)]
+        from: AccountId,
+        beneficiary: String,
         amount: Balance,
     }
 
@@ -59,6 +334,46 @@ mod carbon_token {
         InsufficientBalance,
         /// Returned if not enough allowance to fulfill a request is available.
         InsufficientAllowance,
+        /// Returned if the caller is not the owner.
+        NotOwner,
+        /// Returned if the sender or the recipient is frozen.
+        AccountFrozen,
+        /// Returned if the transfer tax exceeds 100%.
+        InvalidTaxRate,
+        /// Returned if a transfer is below the minimum transfer amount.
+        TransferTooSmall,
+        /// Returned if an arithmetic operation overflows.
+        Overflow,
+        /// Returned if a signature does not match the expected signer.
+        InvalidSignature,
+        /// Returned if a voucher nonce was already redeemed.
+        VoucherUsed,
+        /// Returned if a permit is submitted after its deadline.
+        PermitExpired,
+        /// Returned if no retirement certificate exists for an id.
+        UnknownCertificate,
+        /// Returned if no snapshot exists for an id.
+        UnknownSnapshot,
+        /// Returned if paired input vectors have different lengths.
+        LengthMismatch,
+        /// Returned if the token is transferred, retired or minted after its expiry.
+        TokenExpired,
+        /// Returned if a mint or burn exceeds the per block supply change limit.
+        SupplyRateLimited,
+        /// Returned if neither party of a restricted transfer is whitelisted.
+        NotAllowed,
+        /// Returned if the caller is not allowed to mint.
+        NotAuthorized,
+        /// Returned if minting would exceed the supply cap.
+        CapExceeded,
+        /// Returned if tokens are moved, minted or burned while the token is paused.
+        Paused,
+        /// Returned if the beneficiary still has tokens vesting or unclaimed.
+        VestingExists,
+        /// Returned if the account has no vesting schedule.
+        NoVesting,
+        /// Returned if an allowance no longer has the value the caller expected.
+        AllowanceChanged,
     }
 
     /// Specify the ERC-20 result type.
@@ -67,8 +382,8 @@ mod carbon_token {
     impl CarbonToken {
         /// Create a new ERC-20 contract with an initial supply.
         #[ink(constructor)]
-        pub fn new() -> Self {
-            let total_supply = Balance::default();
+        pub fn new(initial_supply: Balance) -> Self {
+            let total_supply = initial_supply;
             let mut balances = Mapping::default();
             let caller = Self::env().caller();
             balances.insert(caller, &total_supply);
@@ -80,20 +395,247 @@ mod carbon_token {
             });
 
             let allowances = Mapping::default();
+            let mut minters = Mapping::default();
+            minters.insert(caller, &true);
 
             Self {
                 total_supply,
                 balances,
                 allowances,
+                owner: caller,
+                frozen: Mapping::default(),
+                supply_history: Vec::new(),
+                total_retired: 0,
+                total_minted: total_supply,
+                total_burned: 0,
+                transfer_tax_bps: 0,
+                tax_recipient: caller,
+                tax_exempt: Mapping::default(),
+                min_transfer_amount: 0,
+                name: String::new(),
+                symbol: String::new(),
+                decimals: 18,
+                voucher_authority: None,
+                used_voucher_nonces: Mapping::default(),
+                nonces: Mapping::default(),
+                retirements: Mapping::default(),
+                next_certificate_id: 0,
+                auto_retire_sinks: Mapping::default(),
+                expiry_timestamp: None,
+                vintage: None,
+                project_metadata: ProjectMetadata::default(),
+                max_supply_delta_per_block: 0,
+                supply_delta_block: 0,
+                supply_delta_in_block: 0,
+                holder_count: if total_supply > 0 { 1 } else { 0 },
+                paused: false,
+                retired_by_beneficiary: Mapping::default(),
+                minters,
+                cap: None,
+                transfer_restricted: false,
+                transfer_whitelist: Mapping::default(),
+                current_snapshot_id: 0,
+                account_snapshots: Mapping::default(),
+                total_supply_snapshots: Mapping::default(),
+                total_supply_snapshot_count: 0,
+                vesting: Mapping::default(),
             }
         }
 
+        /// Create a new ERC-20 contract without supply, for tokens that are minted later.
+        #[ink(constructor)]
+        #[allow(clippy::should_implement_trait)]
+        pub fn default() -> Self {
+            Self::new(Balance::default())
+        }
+
+        /// Create a new ERC-20 contract with an initial supply and any combination of the optional
+        /// features of the `config`.
+        #[ink(constructor)]
+        pub fn with_config(initial_supply: Balance, config: TokenConfig) -> Self {
+            let mut token = Self::new(initial_supply);
+            token.name = config.name;
+            token.symbol = config.symbol;
+            token.decimals = config.decimals;
+            token.cap = config.cap;
+            token.expiry_timestamp = config.expiry_timestamp;
+            token.vintage = config.vintage;
+            token.project_metadata = config.project_metadata;
+            token
+        }
+
+        /// Create a new ERC-20 contract with an initial supply and the given metadata.
+        #[ink(constructor)]
+        pub fn with_metadata(
+            initial_supply: Balance,
+            name: String,
+            symbol: String,
+            decimals: u8,
+        ) -> Self {
+            Self::with_config(
+                initial_supply,
+                TokenConfig {
+                    name,
+                    symbol,
+                    decimals,
+                    ..TokenConfig::default()
+                },
+            )
+        }
+
+        /// Returns the name of the token.
+        #[ink(message)]
+        pub fn token_name(&self) -> String {
+            self.name.clone()
+        }
+
+        /// Returns the symbol of the token.
+        #[ink(message)]
+        pub fn token_symbol(&self) -> String {
+            self.symbol.clone()
+        }
+
+        /// Returns the number of decimals used to display balances.
+        #[ink(message)]
+        pub fn token_decimals(&self) -> u8 {
+            self.decimals
+        }
+
+        /// Create a new ERC-20 contract with an initial supply and a maximum total supply of `cap`.
+        #[ink(constructor)]
+        pub fn with_cap(initial_supply: Balance, cap: Balance) -> Self {
+            Self::with_config(
+                initial_supply,
+                TokenConfig {
+                    cap: Some(cap),
+                    ..TokenConfig::default()
+                },
+            )
+        }
+
+        /// Returns the maximum total supply, `None` if uncapped.
+        #[ink(message)]
+        pub fn cap(&self) -> Option<Balance> {
+            self.cap
+        }
+
+        /// Create a new ERC-20 contract with an initial supply that can only be burned after
+        /// `expiry_timestamp`.
+        ///
+        /// Expired tokens can no longer be transferred, retired or minted.
+        #[ink(constructor)]
+        pub fn with_expiry(initial_supply: Balance, expiry_timestamp: Timestamp) -> Self {
+            Self::with_config(
+                initial_supply,
+                TokenConfig {
+                    expiry_timestamp: Some(expiry_timestamp),
+                    ..TokenConfig::default()
+                },
+            )
+        }
+
+        /// Returns the timestamp after which the token can only be burned, if any.
+        #[ink(message)]
+        pub fn expiry_timestamp(&self) -> Option<Timestamp> {
+            self.expiry_timestamp
+        }
+
+        /// Returns `true` if the block timestamp is past the token's expiry.
+        #[ink(message)]
+        pub fn is_expired(&self) -> bool {
+            self.expiry_timestamp
+                .is_some_and(|expiry| self.env().block_timestamp() > expiry)
+        }
+
+        /// Create a new ERC-20 contract with an initial supply of credits of a single `vintage` year.
+        ///
+        /// Credits of different vintages are not fungible, so each vintage is issued as its own
+        /// token and pools or registries holding one token only ever hold a single vintage.
+        #[ink(constructor)]
+        pub fn with_vintage(initial_supply: Balance, vintage: u16) -> Self {
+            Self::with_config(
+                initial_supply,
+                TokenConfig {
+                    vintage: Some(vintage),
+                    ..TokenConfig::default()
+                },
+            )
+        }
+
+        /// Returns the vintage year of the credits represented by the token, if any.
+        #[ink(message)]
+        pub fn vintage(&self) -> Option<u16> {
+            self.vintage
+        }
+
+        /// Create a new ERC-20 contract with an initial supply of credits from an offset project.
+        #[ink(constructor)]
+        pub fn with_project_metadata(
+            initial_supply: Balance,
+            project_metadata: ProjectMetadata,
+        ) -> Self {
+            Self::with_config(
+                initial_supply,
+                TokenConfig {
+                    project_metadata,
+                    ..TokenConfig::default()
+                },
+            )
+        }
+
+        /// Returns the offset project underlying the token.
+        #[ink(message)]
+        pub fn project_metadata(&self) -> ProjectMetadata {
+            self.project_metadata.clone()
+        }
+
+        /// Sets the offset project underlying the token.
+        ///
+        /// Only the owner can set the project metadata.
+        #[ink(message)]
+        pub fn set_project_metadata(&mut self, project_metadata: ProjectMetadata) -> Result<()> {
+            self.ensure_owner()?;
+            self.project_metadata = project_metadata;
+            Ok(())
+        }
+
         /// Returns the total token supply.
         #[ink(message)]
         pub fn total_supply(&self) -> Balance {
             self.total_supply
         }
 
+        /// Returns the total amount of tokens retired as carbon offsets.
+        #[ink(message)]
+        pub fn total_retired(&self) -> Balance {
+            self.total_retired
+        }
+
+        /// Returns the total amount of tokens ever minted, including the initial supply.
+        #[ink(message)]
+        pub fn total_minted(&self) -> Balance {
+            self.total_minted
+        }
+
+        /// Returns the total amount of tokens ever burned, excluding retired tokens.
+        #[ink(message)]
+        pub fn total_burned(&self) -> Balance {
+            self.total_burned
+        }
+
+        /// Returns the live total supply, the total minted, the total burned and the total retired.
+        ///
+        /// These always satisfy `minted - burned - retired == total_supply`.
+        #[ink(message)]
+        pub fn supply_breakdown(&self) -> (Balance, Balance, Balance, Balance) {
+            (
+                self.total_supply,
+                self.total_minted,
+                self.total_burned,
+                self.total_retired,
+            )
+        }
+
         /// Returns the account balance for the specified `owner`.
         #[ink(message)]
         pub fn balance_of(&self, owner: AccountId) -> Balance {
@@ -106,27 +648,170 @@ mod carbon_token {
             self.transfer_from_to(&from, &to, value)
         }
 
+        /// Transfers `amounts[i]` of the caller's tokens to `recipients[i]` for every index.
+        ///
+        /// The whole batch fails, and its state changes are reverted, if any transfer fails.
+        #[ink(message)]
+        pub fn transfer_batch(
+            &mut self,
+            recipients: Vec<AccountId>,
+            amounts: Vec<Balance>,
+        ) -> Result<()> {
+            if recipients.len() != amounts.len() {
+                return Err(Error::LengthMismatch);
+            }
+            let from = self.env().caller();
+            let total = amounts
+                .iter()
+                .try_fold(0 as Balance, |total, amount| total.checked_add(*amount))
+                .ok_or(Error::Overflow)?;
+            if self.balance_of(from) < total {
+                return Err(Error::InsufficientBalance);
+            }
+
+            for (to, value) in recipients.iter().zip(amounts) {
+                self.transfer_from_to(&from, to, value)?;
+            }
+            Ok(())
+        }
+
         fn transfer_from_to(
             &mut self,
             from: &AccountId,
             to: &AccountId,
             value: Balance,
         ) -> Result<()> {
-            let from_balance = self.balance_of(*from);
-            if from_balance < value {
-                return Err(Error::InsufficientBalance);
+            self.ensure_not_paused()?;
+            self.ensure_not_expired()?;
+            if self.is_frozen(*from) || self.is_frozen(*to) {
+                return Err(Error::AccountFrozen);
+            }
+            if self.transfer_restricted
+                && !self.is_transfer_whitelisted(*from)
+                && !self.is_transfer_whitelisted(*to)
+            {
+                return Err(Error::NotAllowed);
             }
+            if value != 0 && value < self.min_transfer_amount {
+                return Err(Error::TransferTooSmall);
+            }
+
+            let from_balance = self
+                .balance_of(*from)
+                .checked_sub(value)
+                .ok_or(Error::InsufficientBalance)?;
+            let tax = self.transfer_tax(from, to, value)?;
+            self.set_balance(*from, from_balance);
 
-            self.balances.insert(&from, &(from_balance - value));
-            let to_balance = self.balance_of(*to);
-            self.balances.insert(&to, &(to_balance + value));
+            let received = value.checked_sub(tax).ok_or(Error::Overflow)?;
+            let to_balance = self
+                .balance_of(*to)
+                .checked_add(received)
+                .ok_or(Error::Overflow)?;
+            self.set_balance(*to, to_balance);
 
             self.env().emit_event(Transfer {
                 from: Some(*from),
                 to: Some(*to),
-                value,
+                value: received,
             });
 
+            if tax > 0 {
+                let recipient = self.tax_recipient;
+                let recipient_balance = self
+                    .balance_of(recipient)
+                    .checked_add(tax)
+                    .ok_or(Error::Overflow)?;
+                self.set_balance(recipient, recipient_balance);
+
+                self.env().emit_event(Transfer {
+                    from: Some(*from),
+                    to: Some(recipient),
+                    value: tax,
+                });
+            }
+
+            // Retirement never transfers, so a sink cannot trigger another auto-retirement.
+            if from != to && self.is_auto_retire_sink(*to) {
+                self.retire_tokens(*to, received, String::new(), String::new())?;
+            }
+
+            Ok(())
+        }
+
+        /// Returns the tax charged on a transfer of `value`, zero if either party is exempt.
+        fn transfer_tax(
+            &self,
+            from: &AccountId,
+            to: &AccountId,
+            value: Balance,
+        ) -> Result<Balance> {
+            if self.transfer_tax_bps == 0 || self.is_tax_exempt(*from) || self.is_tax_exempt(*to) {
+                return Ok(0);
+            }
+            value
+                .checked_mul(self.transfer_tax_bps as Balance)
+                .map(|tax| tax / 10_000)
+                .ok_or(Error::Overflow)
+        }
+
+        /// Returns the transfer tax in basis points and the account credited with it.
+        #[ink(message)]
+        pub fn transfer_tax_config(&self) -> (u16, AccountId) {
+            (self.transfer_tax_bps, self.tax_recipient)
+        }
+
+        /// Sets the transfer tax in basis points and the account credited with it.
+        ///
+        /// A zero tax disables it. Only the owner can set the tax.
+        #[ink(message)]
+        pub fn set_transfer_tax(&mut self, tax_bps: u16, recipient: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            if tax_bps > 10_000 {
+                return Err(Error::InvalidTaxRate);
+            }
+
+            self.transfer_tax_bps = tax_bps;
+            self.tax_recipient = recipient;
+
+            Ok(())
+        }
+
+        /// Returns the minimum value of a non-zero transfer.
+        #[ink(message)]
+        pub fn min_transfer_amount(&self) -> Balance {
+            self.min_transfer_amount
+        }
+
+        /// Sets the minimum value of a non-zero transfer, zero disables the check.
+        ///
+        /// Only the owner can set the minimum.
+        #[ink(message)]
+        pub fn set_min_transfer_amount(&mut self, amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.min_transfer_amount = amount;
+            Ok(())
+        }
+
+        /// Returns `true` if transfers from or to the `account` are exempt from the transfer tax.
+        #[ink(message)]
+        pub fn is_tax_exempt(&self, account: AccountId) -> bool {
+            self.tax_exempt.get(account).unwrap_or_default()
+        }
+
+        /// Exempts the `account` from the transfer tax or removes its exemption.
+        ///
+        /// Only the owner can manage exemptions.
+        #[ink(message)]
+        pub fn set_tax_exempt(&mut self, account: AccountId, exempt: bool) -> Result<()> {
+            self.ensure_owner()?;
+
+            if exempt {
+                self.tax_exempt.insert(account, &true);
+            } else {
+                self.tax_exempt.remove(account);
+            }
+
             Ok(())
         }
 
@@ -154,14 +839,24 @@ mod carbon_token {
         #[ink(message)]
         pub fn approve(&mut self, spender: AccountId, value: Balance) -> Result<()> {
             let owner = self.env().caller();
-            self.allowances.insert((owner, spender), &value);
-
-            self.env().emit_event(Approval {
-                owner,
-                spender,
-                value,
-            });
+            self.set_allowance(owner, spender, value);
+            Ok(())
+        }
 
+        /// Sets the allowance granted by the caller to `spenders[i]` to `values[i]` for every index.
+        #[ink(message)]
+        pub fn approve_batch(
+            &mut self,
+            spenders: Vec<AccountId>,
+            values: Vec<Balance>,
+        ) -> Result<()> {
+            if spenders.len() != values.len() {
+                return Err(Error::LengthMismatch);
+            }
+            let owner = self.env().caller();
+            for (spender, value) in spenders.into_iter().zip(values) {
+                self.set_allowance(owner, spender, value);
+            }
             Ok(())
         }
 
@@ -170,118 +865,2149 @@ mod carbon_token {
             self.allowances.get((owner, spender)).unwrap_or_default()
         }
 
+        /// Increases the allowance granted by the caller to `spender` by `delta`.
         #[ink(message)]
-        pub fn mint(&mut self, amount: Balance) -> Result<()> {
-            let caller = Self::env().caller();
-
-            // update total supply
-            let current_total_supply = self.total_supply();
-            self.total_supply = current_total_supply + amount;
-
-            // update minter balance
-            let minter_balance = self.balance_of(caller);
-            self.balances.insert(caller, &(minter_balance + amount));
-
-            Self::env().emit_event(Mint {
-                minter: caller,
-                amount: amount,
-            });
-
+        pub fn increase_allowance(&mut self, spender: AccountId, delta: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            let value = self
+                .allowance(owner, spender)
+                .checked_add(delta)
+                .ok_or(Error::Overflow)?;
+            self.set_allowance(owner, spender, value);
             Ok(())
         }
 
+        /// Decreases the allowance granted by the caller to `spender` by `delta`.
+        ///
+        /// Returns `InsufficientAllowance` if `delta` exceeds the current allowance.
         #[ink(message)]
-        pub fn burn(&mut self, amount: Balance) -> Result<()> {
-            let caller = Self::env().caller();
+        pub fn decrease_allowance(&mut self, spender: AccountId, delta: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            let value = self
+                .allowance(owner, spender)
+                .checked_sub(delta)
+                .ok_or(Error::InsufficientAllowance)?;
+            self.set_allowance(owner, spender, value);
+            Ok(())
+        }
 
-            // check burn able
-            let burner_balance = self.balance_of(caller);
-            let current_total_supply = self.total_supply();
-            if burner_balance < amount || current_total_supply < amount {
-                return Err(Error::InsufficientBalance);
+        /// Sets the allowance granted by the caller to `spender` to `new_allowance`, provided it
+        /// still equals `current_allowance`.
+        ///
+        /// Returns `AllowanceChanged` otherwise, e.g. if `spender` spent part of the allowance
+        /// after the caller read it.
+        #[ink(message)]
+        pub fn safe_approve(
+            &mut self,
+            spender: AccountId,
+            current_allowance: Balance,
+            new_allowance: Balance,
+        ) -> Result<()> {
+            let owner = self.env().caller();
+            if self.allowance(owner, spender) != current_allowance {
+                return Err(Error::AllowanceChanged);
             }
+            self.set_allowance(owner, spender, new_allowance);
+            Ok(())
+        }
 
-            // update total supply
-            let current_total_supply = self.total_supply();
-            self.total_supply = current_total_supply - amount;
+        /// Returns the nonce that the next permit signed by `owner` must use.
+        #[ink(message)]
+        pub fn nonces(&self, owner: AccountId) -> u64 {
+            self.nonces.get(owner).unwrap_or_default()
+        }
 
-            // update burner balance
-            let burner_balance = self.balance_of(caller);
-            self.balances.insert(caller, &(burner_balance - amount));
+        /// Sets the allowance of `spender` over `owner`'s tokens to `value` on behalf of `owner`.
+        ///
+        /// The `signature` is an ECDSA signature by the key of `owner`, whose account id is the
+        /// Blake2x256 hash of its compressed public key, over the Blake2x256 hash of the SCALE
+        /// encoded `(contract, owner, spender, value, nonce, deadline)` tuple, where `nonce` is
+        /// `nonces(owner)`. Returns `PermitExpired` once the block timestamp passes `deadline`.
+        #[ink(message)]
+        pub fn permit(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
+            deadline: Timestamp,
+            signature: [u8; 65],
+        ) -> Result<()> {
+            if self.env().block_timestamp() > deadline {
+                return Err(Error::PermitExpired);
+            }
 
-            Self::env().emit_event(Burn {
-                from: caller,
-                to: AccountId::from([0x0; 32]),
-                amount: amount,
-            });
+            let nonce = self.nonces(owner);
+            let mut message_hash = [0u8; 32];
+            ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(
+                &(
+                    self.env().account_id(),
+                    owner,
+                    spender,
+                    value,
+                    nonce,
+                    deadline,
+                ),
+                &mut message_hash,
+            );
+            let public_key = self
+                .env()
+                .ecdsa_recover(&signature, &message_hash)
+                .map_err(|_| Error::InvalidSignature)?;
+            let mut signer = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&public_key, &mut signer);
+            if AccountId::from(signer) != owner {
+                return Err(Error::InvalidSignature);
+            }
 
+            self.nonces.insert(owner, &(nonce + 1));
+            self.set_allowance(owner, spender, value);
             Ok(())
         }
-    }
-
-    #[cfg(test)]
-    mod tests {
-        use super::*;
 
-        // We define some helper Accounts to make our tests more readable
-        fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
-            ink::env::test::default_accounts::<Environment>()
-        }
+        /// Sets the allowance of `spender` over `owner`'s tokens and emits an `Approval` event.
+        fn set_allowance(&mut self, owner: AccountId, spender: AccountId, value: Balance) {
+            self.allowances.insert((owner, spender), &value);
 
-        fn alice() -> AccountId {
-            default_accounts().alice
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                value,
+            });
         }
 
-        fn bob() -> AccountId {
-            default_accounts().bob
+        /// Retires `amount` of the caller's tokens on behalf of `beneficiary` for the given `reason`
+        /// and returns the certificate id.
+        ///
+        /// Retired tokens are burned, and the retirement is recorded for auditing.
+        #[ink(message)]
+        pub fn retire(
+            &mut self,
+            amount: Balance,
+            beneficiary: String,
+            reason: String,
+        ) -> Result<CertificateId> {
+            let caller = self.env().caller();
+            self.retire_tokens(caller, amount, beneficiary, reason)
+        }
+
+        /// Retires `amount` of `from`'s tokens on behalf of `beneficiary`, spending the caller's allowance.
+        ///
+        /// The retirement is attributed to `from`.
+        #[ink(message)]
+        pub fn retire_from(
+            &mut self,
+            from: AccountId,
+            amount: Balance,
+            beneficiary: String,
+        ) -> Result<()> {
+            let caller = self.env().caller();
+            let allowance = self.allowance(from, caller);
+            if allowance < amount {
+                return Err(Error::InsufficientAllowance);
+            }
+
+            self.retire_tokens(from, amount, beneficiary, String::new())?;
+
+            self.allowances
+                .insert((from, caller), &(allowance - amount));
+
+            Ok(())
+        }
+
+        /// Returns the total amount retired on behalf of `beneficiary`.
+        #[ink(message)]
+        pub fn retired_amount(&self, beneficiary: String) -> Balance {
+            self.retired_by_beneficiary
+                .get(beneficiary)
+                .unwrap_or_default()
+        }
+
+        /// Returns the total amount retired on behalf of each of the `beneficiaries`, in order.
+        ///
+        /// Beneficiaries are not enumerable on chain, so ranking them is left to the caller.
+        #[ink(message)]
+        pub fn retired_amounts(&self, beneficiaries: Vec<String>) -> Vec<Balance> {
+            beneficiaries
+                .into_iter()
+                .map(|beneficiary| self.retired_amount(beneficiary))
+                .collect()
+        }
+
+        /// Returns the retirement record of the certificate `id`.
+        #[ink(message)]
+        pub fn get_retirement(&self, id: CertificateId) -> Option<RetirementRecord> {
+            self.retirements.get(id)
+        }
+
+        /// Returns a Blake2x256 hash over the `(retiree, amount, beneficiary, timestamp)` of the
+        /// certificate `id`, so that registries can publish and verify the retirement off-chain.
+        #[ink(message)]
+        pub fn get_retirement_proof(&self, id: CertificateId) -> Result<[u8; 32]> {
+            let record = self.retirements.get(id).ok_or(Error::UnknownCertificate)?;
+
+            let mut proof = [0u8; 32];
+            ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(
+                &(
+                    record.retiree,
+                    record.amount,
+                    record.beneficiary,
+                    record.timestamp,
+                ),
+                &mut proof,
+            );
+            Ok(proof)
+        }
+
+        /// Burns `amount` of `from`'s tokens, records them as retired and returns the certificate id.
+        fn retire_tokens(
+            &mut self,
+            from: AccountId,
+            amount: Balance,
+            beneficiary: String,
+            reason: String,
+        ) -> Result<CertificateId> {
+            self.ensure_not_paused()?;
+            self.ensure_not_expired()?;
+            let from_balance = self
+                .balance_of(from)
+                .checked_sub(amount)
+                .ok_or(Error::InsufficientBalance)?;
+            let total_supply = self
+                .total_supply
+                .checked_sub(amount)
+                .ok_or(Error::InsufficientBalance)?;
+            let total_retired = self
+                .total_retired
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            let retired = self
+                .retired_amount(beneficiary.clone())
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            let id = self.next_certificate_id;
+            let next_certificate_id = id.checked_add(1).ok_or(Error::Overflow)?;
+            self.track_supply_delta(amount)?;
+
+            self.set_balance(from, from_balance);
+            self.update_supply_snapshot();
+            self.total_supply = total_supply;
+            self.total_retired = total_retired;
+            self.retired_by_beneficiary
+                .insert(beneficiary.clone(), &retired);
+            self.record_supply();
+
+            self.next_certificate_id = next_certificate_id;
+            self.retirements.insert(
+                id,
+                &RetirementRecord {
+                    retiree: from,
+                    beneficiary: beneficiary.clone(),
+                    reason,
+                    amount,
+                    block: self.env().block_number(),
+                    timestamp: self.env().block_timestamp(),
+                },
+            );
+
+            self.env().emit_event(Retire {
+                id,
+                from,
+                beneficiary,
+                amount,
+            });
+
+            Ok(id)
+        }
+
+        /// Sets the compressed ECDSA public key allowed to sign mint vouchers.
+        ///
+        /// Only the owner can set the voucher authority.
+        #[ink(message)]
+        pub fn set_voucher_authority(&mut self, public_key: [u8; 33]) -> Result<()> {
+            self.ensure_owner()?;
+            self.voucher_authority = Some(public_key.to_vec());
+            Ok(())
+        }
+
+        /// Mints `whole_units * 10^decimals` tokens to `to` against a voucher signed by the voucher authority.
+        ///
+        /// The `signature` is an ECDSA signature over the Blake2x256 hash of the SCALE encoded
+        /// `(contract, to, whole_units, nonce)` tuple. Each `nonce` can only be redeemed once.
+        #[ink(message)]
+        pub fn redeem_whole_unit_voucher(
+            &mut self,
+            to: AccountId,
+            whole_units: Balance,
+            nonce: u64,
+            signature: [u8; 65],
+        ) -> Result<()> {
+            if self.used_voucher_nonces.contains(nonce) {
+                return Err(Error::VoucherUsed);
+            }
+
+            let mut message_hash = [0u8; 32];
+            ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(
+                &(self.env().account_id(), to, whole_units, nonce),
+                &mut message_hash,
+            );
+            let signer = self
+                .env()
+                .ecdsa_recover(&signature, &message_hash)
+                .map_err(|_| Error::InvalidSignature)?;
+            if self.voucher_authority.as_deref() != Some(&signer[..]) {
+                return Err(Error::InvalidSignature);
+            }
+
+            let amount = 10u128
+                .checked_pow(self.decimals as u32)
+                .and_then(|unit| whole_units.checked_mul(unit))
+                .ok_or(Error::Overflow)?;
+            // The voucher stands in for the minter role, so the contract itself is the minter
+            self.mint_tokens(self.env().account_id(), to, amount)?;
+            self.used_voucher_nonces.insert(nonce, &());
+
+            Ok(())
+        }
+
+        /// Returns the on/off state of each configurable feature of this deployment.
+        #[ink(message)]
+        pub fn feature_flags(&self) -> Vec<(String, bool)> {
+            ink::prelude::vec![
+                (String::from("transfer_tax"), self.transfer_tax_bps > 0),
+                (
+                    String::from("min_transfer_amount"),
+                    self.min_transfer_amount > 0
+                ),
+                (
+                    String::from("mint_vouchers"),
+                    self.voucher_authority.is_some()
+                ),
+                (String::from("expiry"), self.expiry_timestamp.is_some()),
+                (
+                    String::from("supply_rate_limit"),
+                    self.max_supply_delta_per_block > 0
+                ),
+                (String::from("transfer_whitelist"), self.transfer_restricted),
+                (String::from("cap"), self.cap.is_some()),
+                (String::from("paused"), self.paused),
+            ]
+        }
+
+        /// Returns `true` if transfers require a whitelisted sender or recipient.
+        #[ink(message)]
+        pub fn is_transfer_restricted(&self) -> bool {
+            self.transfer_restricted
+        }
+
+        /// Restricts transfers to those involving a whitelisted party, e.g. during a presale.
+        #[ink(message)]
+        pub fn set_transfer_restricted(&mut self, restricted: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.transfer_restricted = restricted;
+            Ok(())
+        }
+
+        /// Returns `true` if the `account` may transfer while transfers are restricted.
+        #[ink(message)]
+        pub fn is_transfer_whitelisted(&self, account: AccountId) -> bool {
+            self.transfer_whitelist.get(account).unwrap_or_default()
+        }
+
+        /// Allows or disallows the `account` to transfer while transfers are restricted.
+        #[ink(message)]
+        pub fn set_transfer_whitelisted(
+            &mut self,
+            account: AccountId,
+            whitelisted: bool,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            self.transfer_whitelist.insert(account, &whitelisted);
+            Ok(())
+        }
+
+        /// Returns the maximum amount minted, burned and retired within a single block, zero if unlimited.
+        #[ink(message)]
+        pub fn max_supply_delta_per_block(&self) -> Balance {
+            self.max_supply_delta_per_block
+        }
+
+        /// Sets the maximum amount minted, burned and retired within a single block, zero disables the check.
+        #[ink(message)]
+        pub fn set_max_supply_delta_per_block(&mut self, max_delta: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.max_supply_delta_per_block = max_delta;
+            Ok(())
+        }
+
+        /// Returns a Blake2x256 hash over the `(name, symbol, decimals)` metadata, the vintage and the
+        /// project metadata, so that caches can cheaply detect metadata updates.
+        #[ink(message)]
+        pub fn metadata_hash(&self) -> [u8; 32] {
+            let mut hash = [0u8; 32];
+            ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(
+                &(
+                    &self.name,
+                    &self.symbol,
+                    self.decimals,
+                    self.vintage,
+                    &self.project_metadata.registry,
+                    &self.project_metadata.project_id,
+                    &self.project_metadata.methodology,
+                    &self.project_metadata.country,
+                ),
+                &mut hash,
+            );
+            hash
+        }
+
+        /// Returns `true` if tokens transferred to the `account` are retired on receipt.
+        #[ink(message)]
+        pub fn is_auto_retire_sink(&self, account: AccountId) -> bool {
+            self.auto_retire_sinks.get(account).unwrap_or_default()
+        }
+
+        /// Designates the `account` as an auto-retire sink or removes the designation.
+        ///
+        /// Only the owner can manage sinks.
+        #[ink(message)]
+        pub fn set_auto_retire_sink(&mut self, account: AccountId, sink: bool) -> Result<()> {
+            self.ensure_owner()?;
+
+            if sink {
+                self.auto_retire_sinks.insert(account, &true);
+            } else {
+                self.auto_retire_sinks.remove(account);
+            }
+
+            Ok(())
+        }
+
+        /// Returns `true` if the `account` is frozen.
+        #[ink(message)]
+        pub fn is_frozen(&self, account: AccountId) -> bool {
+            self.frozen.get(account).unwrap_or_default()
+        }
+
+        /// Blocks the `account` from sending or receiving tokens. Only the owner can freeze.
+        #[ink(message)]
+        pub fn freeze(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.frozen.insert(account, &true);
+            self.env().emit_event(AccountFrozen { account });
+            Ok(())
+        }
+
+        /// Allows a frozen `account` to send and receive tokens again. Only the owner can unfreeze.
+        #[ink(message)]
+        pub fn unfreeze(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.frozen.remove(account);
+            self.env().emit_event(AccountUnfrozen { account });
+            Ok(())
+        }
+
+        /// Returns the frozen status of each of the given `accounts`, in order.
+        ///
+        /// Used together with `import_frozen` to carry the frozen list over to a new deployment.
+        #[ink(message)]
+        pub fn export_frozen(&self, accounts: Vec<AccountId>) -> Vec<bool> {
+            accounts
+                .into_iter()
+                .map(|account| self.is_frozen(account))
+                .collect()
+        }
+
+        /// Sets the frozen status of each of the given accounts. Only the owner can import.
+        #[ink(message)]
+        pub fn import_frozen(&mut self, entries: Vec<(AccountId, bool)>) -> Result<()> {
+            self.ensure_owner()?;
+
+            for (account, frozen) in entries {
+                if frozen {
+                    self.frozen.insert(account, &true);
+                } else {
+                    self.frozen.remove(account);
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Returns the supply, holder and pause statistics of the token in a single call.
+        #[ink(message)]
+        pub fn stats(&self) -> TokenStats {
+            TokenStats {
+                total_supply: self.total_supply,
+                total_burned: self.total_burned,
+                total_retired: self.total_retired,
+                holder_count: self.holder_count,
+                paused: self.paused,
+            }
+        }
+
+        /// Returns the recorded `(block_number, total_supply)` history, oldest first.
+        #[ink(message)]
+        pub fn get_supply_history(&self) -> Vec<(BlockNumber, Balance)> {
+            self.supply_history.clone()
+        }
+
+        /// Sets the `balance` of `account`, keeping the holder count and snapshots up to date.
+        fn set_balance(&mut self, account: AccountId, balance: Balance) {
+            let previous = self.balance_of(account);
+            let mut snapshots = self.account_snapshots.get(account).unwrap_or_default();
+            if self.record_snapshot(&mut snapshots, previous) {
+                self.account_snapshots.insert(account, &snapshots);
+            }
+            if previous == 0 && balance > 0 {
+                self.holder_count += 1;
+            } else if previous > 0 && balance == 0 {
+                self.holder_count -= 1;
+            }
+            self.balances.insert(account, &balance);
+        }
+
+        /// Records the current total supply for the latest snapshot if it was not recorded yet.
+        fn update_supply_snapshot(&mut self) {
+            let count = self.total_supply_snapshot_count;
+            let last_id = match count.checked_sub(1) {
+                Some(last) => self.supply_snapshot(last).0,
+                None => 0,
+            };
+            if last_id < self.current_snapshot_id {
+                self.total_supply_snapshots
+                    .insert(count, &(self.current_snapshot_id, self.total_supply));
+                self.total_supply_snapshot_count = count + 1;
+            }
+        }
+
+        /// Returns the `(snapshot_id, total_supply)` pair recorded at `index`.
+        fn supply_snapshot(&self, index: u32) -> (SnapshotId, Balance) {
+            self.total_supply_snapshots.get(index).unwrap_or_default()
+        }
+
+        /// Appends `value` for the latest snapshot to `snapshots` unless one was already recorded.
+        ///
+        /// Returns `true` if `snapshots` changed.
+        fn record_snapshot(&self, snapshots: &mut SnapshotHistory, value: Balance) -> bool {
+            let last_id = snapshots.last().map(|(id, _)| *id).unwrap_or_default();
+            if last_id < self.current_snapshot_id {
+                snapshots.push((self.current_snapshot_id, value));
+                true
+            } else {
+                false
+            }
+        }
+
+        /// Returns the value recorded for `snapshot_id` among the `count` snapshots returned by
+        /// `snapshot`, or `current` if the value did not change since the snapshot.
+        fn snapshot_value(
+            &self,
+            count: u32,
+            snapshot: impl Fn(u32) -> (SnapshotId, Balance),
+            snapshot_id: SnapshotId,
+            current: Balance,
+        ) -> Result<Balance> {
+            if snapshot_id == 0 || snapshot_id > self.current_snapshot_id {
+                return Err(Error::UnknownSnapshot);
+            }
+            // The first value recorded at or after the snapshot is the one held when it was taken
+            let (mut low, mut high) = (0, count);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                if snapshot(mid).0 < snapshot_id {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            Ok(if low < count {
+                snapshot(low).1
+            } else {
+                current
+            })
+        }
+
+        /// Appends the current total supply to the history, evicting the oldest entry when full.
+        fn record_supply(&mut self) {
+            if self.supply_history.len() >= super::SUPPLY_HISTORY_LIMIT {
+                self.supply_history.remove(0);
+            }
+            let block = self.env().block_number();
+            self.supply_history.push((block, self.total_supply));
+        }
+
+        /// Adds `amount` to the supply changed within the current block.
+        ///
+        /// Returns `SupplyRateLimited` if this exceeds the per block limit.
+        fn track_supply_delta(&mut self, amount: Balance) -> Result<()> {
+            if self.max_supply_delta_per_block == 0 {
+                return Ok(());
+            }
+            let block = self.env().block_number();
+            let delta_in_block = if block == self.supply_delta_block {
+                self.supply_delta_in_block
+            } else {
+                0
+            };
+            let delta_in_block = delta_in_block
+                .checked_add(amount)
+                .filter(|delta| *delta <= self.max_supply_delta_per_block)
+                .ok_or(Error::SupplyRateLimited)?;
+            self.supply_delta_block = block;
+            self.supply_delta_in_block = delta_in_block;
+            Ok(())
+        }
+
+        /// Returns `CapExceeded` if `total_supply` is above the supply cap.
+        fn ensure_within_cap(&self, total_supply: Balance) -> Result<()> {
+            match self.cap {
+                Some(cap) if total_supply > cap => Err(Error::CapExceeded),
+                _ => Ok(()),
+            }
+        }
+
+        /// Returns the amount of the `schedule` unlocked at the current block timestamp.
+        fn unlocked(&self, schedule: &VestingSchedule) -> Balance {
+            let elapsed = self.env().block_timestamp().saturating_sub(schedule.start);
+            if elapsed >= schedule.duration {
+                return schedule.total;
+            }
+            let (elapsed, duration) = (elapsed as Balance, schedule.duration as Balance);
+            schedule
+                .total
+                .checked_mul(elapsed)
+                .map_or(schedule.total / duration * elapsed, |vested| {
+                    vested / duration
+                })
+        }
+
+        /// Returns `Paused` if the token is paused.
+        fn ensure_not_paused(&self) -> Result<()> {
+            if self.paused {
+                return Err(Error::Paused);
+            }
+            Ok(())
+        }
+
+        /// Returns `TokenExpired` if the token is past its expiry.
+        fn ensure_not_expired(&self) -> Result<()> {
+            if self.is_expired() {
+                return Err(Error::TokenExpired);
+            }
+            Ok(())
+        }
+
+        /// Returns `NotOwner` if the caller is not the owner.
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner);
+            }
+            Ok(())
+        }
+
+        /// Takes a snapshot of all balances and the total supply and returns its id.
+        ///
+        /// Only the owner can take snapshots.
+        #[ink(message)]
+        pub fn snapshot(&mut self) -> Result<SnapshotId> {
+            self.ensure_owner()?;
+            let id = self
+                .current_snapshot_id
+                .checked_add(1)
+                .ok_or(Error::Overflow)?;
+            self.current_snapshot_id = id;
+            self.env().emit_event(Snapshot { id });
+            Ok(id)
+        }
+
+        /// Returns the id of the latest snapshot, zero if none was taken.
+        #[ink(message)]
+        pub fn current_snapshot_id(&self) -> SnapshotId {
+            self.current_snapshot_id
+        }
+
+        /// Returns the balance of `account` when the snapshot `snapshot_id` was taken.
+        ///
+        /// Returns `UnknownSnapshot` if no snapshot with this id was taken.
+        #[ink(message)]
+        pub fn balance_of_at(
+            &self,
+            account: AccountId,
+            snapshot_id: SnapshotId,
+        ) -> Result<Balance> {
+            let snapshots = self.account_snapshots.get(account).unwrap_or_default();
+            self.snapshot_value(
+                snapshots.len() as u32,
+                |index| snapshots[index as usize],
+                snapshot_id,
+                self.balance_of(account),
+            )
+        }
+
+        /// Returns the total supply when the snapshot `snapshot_id` was taken.
+        ///
+        /// Returns `UnknownSnapshot` if no snapshot with this id was taken.
+        #[ink(message)]
+        pub fn total_supply_at(&self, snapshot_id: SnapshotId) -> Result<Balance> {
+            self.snapshot_value(
+                self.total_supply_snapshot_count,
+                |index| self.supply_snapshot(index),
+                snapshot_id,
+                self.total_supply,
+            )
+        }
+
+        /// Returns the account allowed to administer the token.
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        /// Transfers the ownership of the token to `new_owner`.
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            let previous = self.owner;
+            self.owner = new_owner;
+
+            self.env().emit_event(OwnershipTransferred {
+                previous,
+                new: new_owner,
+            });
+
+            Ok(())
+        }
+
+        /// Returns `true` if transfers, mints and burns are halted.
+        #[ink(message)]
+        pub fn paused(&self) -> bool {
+            self.paused
+        }
+
+        /// Halts transfers, mints and burns, e.g. during an audit or legal hold.
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            self.paused = true;
+            let by = self.env().caller();
+            self.env().emit_event(Paused { by });
+            Ok(())
+        }
+
+        /// Resumes transfers, mints and burns.
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            self.paused = false;
+            let by = self.env().caller();
+            self.env().emit_event(Unpaused { by });
+            Ok(())
+        }
+
+        /// Returns `true` if the `account` may mint.
+        #[ink(message)]
+        pub fn is_minter(&self, account: AccountId) -> bool {
+            self.minters.get(account).unwrap_or_default()
+        }
+
+        /// Allows the `account` to mint.
+        #[ink(message)]
+        pub fn grant_minter(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.minters.insert(account, &true);
+            self.env().emit_event(MinterGranted { account });
+            Ok(())
+        }
+
+        /// Revokes the `account`'s permission to mint.
+        #[ink(message)]
+        pub fn revoke_minter(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.minters.remove(account);
+            self.env().emit_event(MinterRevoked { account });
+            Ok(())
+        }
+
+        /// Mints `amount` tokens to the caller, who must be a minter.
+        #[ink(message)]
+        pub fn mint(&mut self, amount: Balance) -> Result<()> {
+            let caller = Self::env().caller();
+            self.mint_to(caller, amount)
+        }
+
+        /// Mints `amount` tokens to `to`. The caller must be a minter.
+        #[ink(message)]
+        pub fn mint_to(&mut self, to: AccountId, amount: Balance) -> Result<()> {
+            let caller = Self::env().caller();
+            if !self.is_minter(caller) {
+                return Err(Error::NotAuthorized);
+            }
+            self.mint_tokens(caller, to, amount)
+        }
+
+        /// Mints `amount` tokens to `to` on behalf of `minter`.
+        fn mint_tokens(&mut self, minter: AccountId, to: AccountId, amount: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.ensure_not_expired()?;
+            let total_supply = self
+                .total_supply
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            self.ensure_within_cap(total_supply)?;
+            let total_minted = self
+                .total_minted
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            let to_balance = self
+                .balance_of(to)
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            self.track_supply_delta(amount)?;
+
+            // update total supply
+            self.update_supply_snapshot();
+            self.total_supply = total_supply;
+            self.total_minted = total_minted;
+
+            // update recipient balance
+            self.set_balance(to, to_balance);
+
+            self.record_supply();
+
+            Self::env().emit_event(Mint { minter, to, amount });
+            Self::env().emit_event(Transfer {
+                from: None,
+                to: Some(to),
+                value: amount,
+            });
+
+            Ok(())
+        }
+
+        /// Mints `total` tokens vesting linearly to `beneficiary` over `duration` milliseconds
+        /// from now. The caller must be a minter.
+        ///
+        /// The tokens are held by the contract until claimed with `claim_vested`. Fails if the
+        /// beneficiary's previous schedule is not fully claimed.
+        #[ink(message)]
+        pub fn create_vesting(
+            &mut self,
+            beneficiary: AccountId,
+            total: Balance,
+            duration: Timestamp,
+        ) -> Result<()> {
+            if let Some(schedule) = self.vesting.get(beneficiary) {
+                if schedule.claimed < schedule.total {
+                    return Err(Error::VestingExists);
+                }
+            }
+            self.mint_to(self.env().account_id(), total)?;
+
+            let start = self.env().block_timestamp();
+            self.vesting.insert(
+                beneficiary,
+                &VestingSchedule {
+                    total,
+                    claimed: 0,
+                    start,
+                    duration,
+                },
+            );
+            self.env().emit_event(VestingCreated {
+                beneficiary,
+                total,
+                start,
+                duration,
+            });
+            Ok(())
+        }
+
+        /// Returns the vesting schedule of `account`, if any.
+        #[ink(message)]
+        pub fn vesting_schedule(&self, account: AccountId) -> Option<VestingSchedule> {
+            self.vesting.get(account)
+        }
+
+        /// Returns the amount of `account`'s vesting tokens unlocked so far, including those
+        /// already claimed.
+        #[ink(message)]
+        pub fn vested_amount(&self, account: AccountId) -> Balance {
+            self.vesting
+                .get(account)
+                .map_or(0, |schedule| self.unlocked(&schedule))
+        }
+
+        /// Releases the caller's unlocked and unclaimed vesting tokens to the caller.
+        ///
+        /// The tokens were minted at vesting creation, so releasing them is not subject to the
+        /// transfer tax, minimum, restrictions or token expiry. Returns the amount claimed.
+        #[ink(message)]
+        pub fn claim_vested(&mut self) -> Result<Balance> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut schedule = self.vesting.get(caller).ok_or(Error::NoVesting)?;
+            let amount = self.unlocked(&schedule) - schedule.claimed;
+            if amount == 0 {
+                return Ok(0);
+            }
+            let contract = self.env().account_id();
+            let contract_balance = self
+                .balance_of(contract)
+                .checked_sub(amount)
+                .ok_or(Error::InsufficientBalance)?;
+            let caller_balance = self
+                .balance_of(caller)
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+
+            schedule.claimed += amount;
+            self.vesting.insert(caller, &schedule);
+            self.set_balance(contract, contract_balance);
+            self.set_balance(caller, caller_balance);
+
+            self.env().emit_event(Transfer {
+                from: Some(contract),
+                to: Some(caller),
+                value: amount,
+            });
+
+            Ok(amount)
+        }
+
+        #[ink(message)]
+        pub fn burn(&mut self, amount: Balance) -> Result<()> {
+            let caller = Self::env().caller();
+            self.burn_tokens(caller, amount)
+        }
+
+        /// Burns `amount` of `from`'s tokens, spending the caller's allowance.
+        #[ink(message)]
+        pub fn burn_from(&mut self, from: AccountId, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            let allowance = self.allowance(from, caller);
+            if allowance < amount {
+                return Err(Error::InsufficientAllowance);
+            }
+
+            self.burn_tokens(from, amount)?;
+
+            self.allowances
+                .insert((from, caller), &(allowance - amount));
+
+            Ok(())
+        }
+
+        /// Burns `amount` of `from`'s tokens.
+        fn burn_tokens(&mut self, from: AccountId, amount: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            // check burn able
+            let burner_balance = self
+                .balance_of(from)
+                .checked_sub(amount)
+                .ok_or(Error::InsufficientBalance)?;
+            let total_supply = self
+                .total_supply
+                .checked_sub(amount)
+                .ok_or(Error::InsufficientBalance)?;
+            let total_burned = self
+                .total_burned
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            self.track_supply_delta(amount)?;
+
+            // update total supply
+            self.update_supply_snapshot();
+            self.total_supply = total_supply;
+            self.total_burned = total_burned;
+
+            // update burner balance
+            self.set_balance(from, burner_balance);
+
+            self.record_supply();
+
+            Self::env().emit_event(Burn {
+                burner: from,
+                amount,
+            });
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        // We define some helper Accounts to make our tests more readable
+        fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+            ink::env::test::default_accounts::<Environment>()
+        }
+
+        fn alice() -> AccountId {
+            default_accounts().alice
+        }
+
+        fn bob() -> AccountId {
+            default_accounts().bob
+        }
+
+        fn charlie() -> AccountId {
+            default_accounts().charlie
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let contract = CarbonToken::new(777);
+            assert_eq!(contract.total_supply(), 777);
+        }
+
+        #[ink::test]
+        fn balance_works() {
+            let contract = CarbonToken::new(100);
+            assert_eq!(contract.total_supply(), 100);
+            assert_eq!(contract.balance_of(alice()), 100);
+            assert_eq!(contract.balance_of(bob()), 0);
+        }
+
+        #[ink::test]
+        fn transfer_works() {
+            let mut contract = CarbonToken::new(100);
+            assert_eq!(contract.balance_of(alice()), 100);
+            assert!(contract.transfer(bob(), 10).is_ok());
+            assert_eq!(contract.balance_of(bob()), 10);
+            assert!(contract.transfer(bob(), 100).is_err());
+        }
+
+        #[ink::test]
+        fn transfer_from_works() {
+            let mut contract = CarbonToken::new(100);
+            assert_eq!(contract.balance_of(alice()), 100);
+            let _ = contract.approve(alice(), 20);
+            let _ = contract.transfer_from(alice(), bob(), 10);
+            assert_eq!(contract.balance_of(bob()), 10);
+        }
+
+        #[ink::test]
+        fn transfer_from_by_spender_works() {
+            let mut contract = CarbonToken::new(100);
+            assert!(contract.approve(bob(), 30).is_ok());
+            assert_eq!(contract.allowance(alice(), bob()), 30);
+            assert_eq!(contract.allowance(bob(), alice()), 0);
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract.transfer_from(alice(), charlie(), 20).is_ok());
+            assert_eq!(contract.balance_of(alice()), 80);
+            assert_eq!(contract.balance_of(charlie()), 20);
+            assert_eq!(contract.allowance(alice(), bob()), 10);
+            assert_eq!(
+                contract.transfer_from(alice(), charlie(), 20),
+                Err(Error::InsufficientAllowance)
+            );
+        }
+
+        #[ink::test]
+        fn allowances_works() {
+            let mut contract = CarbonToken::new(100);
+            assert_eq!(contract.balance_of(alice()), 100);
+            let _ = contract.approve(alice(), 200);
+            assert_eq!(contract.allowance(alice(), alice()), 200);
+
+            assert!(contract.transfer_from(alice(), bob(), 50).is_ok());
+            assert_eq!(contract.balance_of(bob()), 50);
+            assert_eq!(contract.allowance(alice(), alice()), 150);
+
+            assert!(contract.transfer_from(alice(), bob(), 100).is_err());
+            assert_eq!(contract.balance_of(bob()), 50);
+            assert_eq!(contract.allowance(alice(), alice()), 150);
+        }
+
+        #[ink::test]
+        fn frozen_import_export_works() {
+            let mut old = CarbonToken::default();
+            assert!(old
+                .import_frozen(vec![(bob(), true), (charlie(), false)])
+                .is_ok());
+            let exported = old.export_frozen(vec![alice(), bob(), charlie()]);
+            assert_eq!(exported, vec![false, true, false]);
+
+            let mut new = CarbonToken::default();
+            let entries = vec![alice(), bob(), charlie()]
+                .into_iter()
+                .zip(exported.clone())
+                .collect();
+            assert!(new.import_frozen(entries).is_ok());
+            assert_eq!(new.export_frozen(vec![alice(), bob(), charlie()]), exported);
+            assert!(new.is_frozen(bob()));
+        }
+
+        #[ink::test]
+        fn import_frozen_only_owner() {
+            let mut contract = CarbonToken::default();
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(
+                contract.import_frozen(vec![(alice(), true)]),
+                Err(Error::NotOwner)
+            );
+        }
+
+        #[ink::test]
+        fn supply_history_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.get_supply_history().is_empty());
+
+            assert!(contract.mint(100).is_ok());
+            ink::env::test::advance_block::<Environment>();
+            assert!(contract.burn(40).is_ok());
+            ink::env::test::advance_block::<Environment>();
+            assert!(contract.mint(10).is_ok());
+
+            assert_eq!(
+                contract.get_supply_history(),
+                vec![(0, 100), (1, 60), (2, 70)]
+            );
         }
 
         #[ink::test]
-        fn new_works() {
-            let contract = CarbonToken::new(777);
-            assert_eq!(contract.total_supply(), 777);
+        fn supply_history_is_bounded() {
+            let mut contract = CarbonToken::default();
+            for _ in 0..crate::SUPPLY_HISTORY_LIMIT + 1 {
+                assert!(contract.mint(1).is_ok());
+            }
+
+            let history = contract.get_supply_history();
+            assert_eq!(history.len(), crate::SUPPLY_HISTORY_LIMIT);
+            assert_eq!(history[0], (0, 2));
         }
 
         #[ink::test]
-        fn balance_works() {
-            let contract = CarbonToken::new(100);
-            assert_eq!(contract.total_supply(), 100);
+        fn retire_from_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(100).is_ok());
+            assert!(contract.approve(bob(), 30).is_ok());
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract
+                .retire_from(alice(), 20, String::from("Acme"))
+                .is_ok());
+            assert_eq!(contract.balance_of(alice()), 80);
+            assert_eq!(contract.total_supply(), 80);
+            assert_eq!(contract.total_retired(), 20);
+            assert_eq!(contract.allowance(alice(), bob()), 10);
+        }
+
+        #[ink::test]
+        fn retire_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(100).is_ok());
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::set_block_timestamp::<Environment>(12);
+            assert_eq!(
+                contract.retire(30, String::from("Acme"), String::from("2023 flights")),
+                Ok(0)
+            );
+            assert_eq!(contract.balance_of(alice()), 70);
+            assert_eq!(contract.total_supply(), 70);
+            assert_eq!(contract.total_retired(), 30);
+            assert_eq!(
+                contract.get_retirement(0),
+                Some(RetirementRecord {
+                    retiree: alice(),
+                    beneficiary: String::from("Acme"),
+                    reason: String::from("2023 flights"),
+                    amount: 30,
+                    block: 1,
+                    timestamp: 12,
+                })
+            );
+
+            assert_eq!(
+                contract.retire(20, String::from("Globex"), String::new()),
+                Ok(1)
+            );
+            assert_eq!(contract.total_retired(), 50);
+            assert_eq!(
+                contract.retire(51, String::from("Acme"), String::new()),
+                Err(Error::InsufficientBalance)
+            );
+        }
+
+        #[ink::test]
+        fn retire_from_fails_without_allowance() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(100).is_ok());
+            assert!(contract.approve(bob(), 10).is_ok());
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(
+                contract.retire_from(alice(), 20, String::from("Acme")),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(contract.total_retired(), 0);
+        }
+
+        #[ink::test]
+        fn retire_from_fails_without_balance() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(10).is_ok());
+            assert!(contract.approve(bob(), 50).is_ok());
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(
+                contract.retire_from(alice(), 20, String::from("Acme")),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(contract.allowance(alice(), bob()), 50);
+        }
+
+        #[ink::test]
+        fn tax_exemption_works() {
+            let accounts = default_accounts();
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(10_000).is_ok());
+            assert!(contract.set_transfer_tax(100, accounts.eve).is_ok());
+            assert!(contract.set_tax_exempt(charlie(), true).is_ok());
+
+            // A normal transfer pays 1% tax
+            assert!(contract.transfer(bob(), 1_000).is_ok());
+            assert_eq!(contract.balance_of(bob()), 990);
+            assert_eq!(contract.balance_of(accounts.eve), 10);
+
+            // A transfer involving an exempt account pays none
+            assert!(contract.transfer(charlie(), 1_000).is_ok());
+            assert_eq!(contract.balance_of(charlie()), 1_000);
+            assert_eq!(contract.balance_of(accounts.eve), 10);
+        }
+
+        #[ink::test]
+        fn transfer_tax_overflow_fails() {
+            let mut contract = CarbonToken::new(Balance::MAX);
+            assert!(contract.set_transfer_tax(100, charlie()).is_ok());
+
+            assert_eq!(
+                contract.transfer(bob(), Balance::MAX / 2),
+                Err(Error::Overflow)
+            );
+            assert_eq!(contract.balance_of(alice()), Balance::MAX);
+            assert_eq!(contract.balance_of(bob()), 0);
+        }
+
+        #[ink::test]
+        fn set_tax_exempt_only_owner() {
+            let mut contract = CarbonToken::default();
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.set_tax_exempt(bob(), true), Err(Error::NotOwner));
+            assert_eq!(contract.set_transfer_tax(100, bob()), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn min_transfer_amount_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(100).is_ok());
+            assert!(contract.set_min_transfer_amount(10).is_ok());
+
+            assert_eq!(contract.transfer(bob(), 9), Err(Error::TransferTooSmall));
+            assert!(contract.transfer(bob(), 10).is_ok());
+            assert_eq!(contract.balance_of(bob()), 10);
+
+            // A zero setting disables the minimum
+            assert!(contract.set_min_transfer_amount(0).is_ok());
+            assert!(contract.transfer(bob(), 1).is_ok());
+        }
+
+        /// Compressed public key of the voucher authority used in the tests.
+        const VOUCHER_AUTHORITY: [u8; 33] = [
+            0x02, 0x4e, 0x3b, 0x81, 0xaf, 0x9c, 0x22, 0x34, 0xca, 0xd0, 0x9d, 0x67, 0x9c, 0xe6,
+            0x03, 0x5e, 0xd1, 0x39, 0x23, 0x47, 0xce, 0x64, 0xce, 0x40, 0x5f, 0x5d, 0xcd, 0x36,
+            0x22, 0x8a, 0x25, 0xde, 0x6e,
+        ];
+
+        /// Voucher authority signature over `(contract, bob, 10, 7)` with the contract at `[0x42; 32]`.
+        const VOUCHER_SIGNATURE: [u8; 65] = [
+            0x93, 0x77, 0xc3, 0x12, 0x14, 0x5a, 0x5a, 0xfb, 0x91, 0x1b, 0xf9, 0xe8, 0xc0, 0x67,
+            0xbc, 0xf6, 0x09, 0x4c, 0x53, 0x36, 0x03, 0x68, 0x78, 0x50, 0xdf, 0x50, 0x2b, 0x61,
+            0x29, 0x0b, 0xbf, 0x5e, 0x73, 0x37, 0x8e, 0x2b, 0x9e, 0x80, 0xe6, 0xac, 0x5c, 0xff,
+            0x89, 0x44, 0x5e, 0xe5, 0x26, 0xd9, 0x84, 0x56, 0xc6, 0xa2, 0xd2, 0x60, 0x71, 0x19,
+            0xa6, 0xa0, 0x92, 0xf1, 0x9e, 0xe1, 0x5e, 0xed, 0x00,
+        ];
+
+        #[ink::test]
+        fn redeem_whole_unit_voucher_works() {
+            ink::env::test::set_callee::<Environment>(AccountId::from([0x42; 32]));
+            let mut contract = CarbonToken::default();
+            assert!(contract.set_voucher_authority(VOUCHER_AUTHORITY).is_ok());
+
+            let events = ink::env::test::recorded_events().count();
+            assert!(contract
+                .redeem_whole_unit_voucher(bob(), 10, 7, VOUCHER_SIGNATURE)
+                .is_ok());
+            // One Mint & one Transfer event
+            assert_eq!(ink::env::test::recorded_events().count(), events + 2);
+            assert_eq!(contract.balance_of(bob()), 10 * 10u128.pow(18));
+            assert_eq!(contract.total_supply(), 10 * 10u128.pow(18));
+
+            assert_eq!(
+                contract.redeem_whole_unit_voucher(bob(), 10, 7, VOUCHER_SIGNATURE),
+                Err(Error::VoucherUsed)
+            );
+        }
+
+        #[ink::test]
+        fn redeem_whole_unit_voucher_rejects_tampered_amount() {
+            ink::env::test::set_callee::<Environment>(AccountId::from([0x42; 32]));
+            let mut contract = CarbonToken::default();
+            assert!(contract.set_voucher_authority(VOUCHER_AUTHORITY).is_ok());
+
+            assert_eq!(
+                contract.redeem_whole_unit_voucher(bob(), 1_000, 7, VOUCHER_SIGNATURE),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(contract.balance_of(bob()), 0);
+        }
+
+        #[ink::test]
+        fn feature_flags_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.feature_flags().iter().all(|(_, enabled)| !enabled));
+
+            assert!(contract.set_transfer_tax(100, bob()).is_ok());
+            assert!(contract.set_min_transfer_amount(10).is_ok());
+            assert_eq!(
+                contract.feature_flags(),
+                vec![
+                    (String::from("transfer_tax"), true),
+                    (String::from("min_transfer_amount"), true),
+                    (String::from("mint_vouchers"), false),
+                    (String::from("expiry"), false),
+                    (String::from("supply_rate_limit"), false),
+                    (String::from("transfer_whitelist"), false),
+                    (String::from("cap"), false),
+                    (String::from("paused"), false),
+                ]
+            );
+
+            assert!(contract.pause().is_ok());
+            assert!(contract
+                .feature_flags()
+                .contains(&(String::from("paused"), true)));
+        }
+
+        #[ink::test]
+        fn retirement_proof_works() {
+            let mut contract = CarbonToken::default();
+            assert_eq!(
+                contract.get_retirement_proof(0),
+                Err(Error::UnknownCertificate)
+            );
+            assert!(contract.mint(100).is_ok());
+            assert!(contract.approve(alice(), 100).is_ok());
+            assert!(contract
+                .retire_from(alice(), 20, String::from("Acme"))
+                .is_ok());
+
+            let record = contract.get_retirement(0).unwrap();
+            let mut expected = [0u8; 32];
+            ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(
+                &(
+                    alice(),
+                    20 as Balance,
+                    String::from("Acme"),
+                    record.timestamp,
+                ),
+                &mut expected,
+            );
+
+            let proof = contract.get_retirement_proof(0).unwrap();
+            assert_eq!(proof, expected);
+            assert_eq!(contract.get_retirement_proof(0), Ok(proof));
+            assert_eq!(
+                contract.get_retirement_proof(1),
+                Err(Error::UnknownCertificate)
+            );
+        }
+
+        #[ink::test]
+        fn auto_retire_sink_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(100).is_ok());
+            assert!(contract.set_auto_retire_sink(charlie(), true).is_ok());
+
+            assert!(contract.transfer(charlie(), 30).is_ok());
+            assert_eq!(contract.balance_of(charlie()), 0);
+            assert_eq!(contract.total_retired(), 30);
+            assert_eq!(contract.total_supply(), 70);
+            assert_eq!(contract.get_retirement(0).unwrap().amount, 30);
+
+            // Regular accounts keep what they receive
+            assert!(contract.transfer(bob(), 30).is_ok());
+            assert_eq!(contract.balance_of(bob()), 30);
+            assert_eq!(contract.total_retired(), 30);
+        }
+
+        #[ink::test]
+        fn supply_breakdown_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(1_000).is_ok());
+            assert!(contract.burn(100).is_ok());
+            assert!(contract.approve(alice(), 200).is_ok());
+            assert!(contract
+                .retire_from(alice(), 200, String::from("Acme"))
+                .is_ok());
+            assert!(contract.mint(50).is_ok());
+
+            let (supply, minted, burned, retired) = contract.supply_breakdown();
+            assert_eq!((supply, minted, burned, retired), (750, 1_050, 100, 200));
+            assert_eq!(minted - burned - retired, supply);
+            assert_eq!(supply, contract.total_supply());
+        }
+
+        #[ink::test]
+        fn token_expiry_works() {
+            let mut contract = CarbonToken::with_expiry(100, 1_000);
+            assert_eq!(contract.expiry_timestamp(), Some(1_000));
             assert_eq!(contract.balance_of(alice()), 100);
+
+            ink::env::test::set_block_timestamp::<Environment>(1_000);
+            assert!(!contract.is_expired());
+            assert!(contract.transfer(bob(), 10).is_ok());
+            assert!(contract
+                .retire(10, String::from("Acme"), String::new())
+                .is_ok());
+
+            ink::env::test::set_block_timestamp::<Environment>(1_001);
+            assert!(contract.is_expired());
+            assert_eq!(contract.transfer(bob(), 10), Err(Error::TokenExpired));
+            assert!(contract.approve(bob(), 10).is_ok());
+            assert_eq!(
+                contract.retire(10, String::from("Acme"), String::new()),
+                Err(Error::TokenExpired)
+            );
+            assert_eq!(contract.mint(10), Err(Error::TokenExpired));
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(
+                contract.transfer_from(alice(), bob(), 10),
+                Err(Error::TokenExpired)
+            );
+            assert_eq!(
+                contract.retire_from(alice(), 10, String::from("Acme")),
+                Err(Error::TokenExpired)
+            );
+            assert_eq!(contract.total_retired(), 10);
+
+            // Burning is still possible after expiry
+            assert!(contract.burn(10).is_ok());
             assert_eq!(contract.balance_of(bob()), 0);
+            ink::env::test::set_caller::<Environment>(alice());
+            assert!(contract.burn(80).is_ok());
+            assert_eq!(contract.total_supply(), 0);
         }
 
         #[ink::test]
-        fn transfer_works() {
+        fn supply_rate_limit_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.set_max_supply_delta_per_block(100).is_ok());
+            assert_eq!(contract.max_supply_delta_per_block(), 100);
+
+            // All in one block is rejected
+            assert!(contract.mint(60).is_ok());
+            assert_eq!(contract.mint(60), Err(Error::SupplyRateLimited));
+            assert_eq!(contract.burn(50), Err(Error::SupplyRateLimited));
+            assert_eq!(
+                contract.retire(50, String::from("Acme"), String::new()),
+                Err(Error::SupplyRateLimited)
+            );
+
+            // Split across two blocks is accepted
+            ink::env::test::advance_block::<Environment>();
+            assert!(contract.mint(60).is_ok());
+            assert_eq!(contract.total_supply(), 120);
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(
+                contract.set_max_supply_delta_per_block(0),
+                Err(Error::NotOwner)
+            );
+        }
+
+        #[ink::test]
+        fn stats_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(100).is_ok());
+            assert!(contract.transfer(bob(), 40).is_ok());
+            assert!(contract.burn(10).is_ok());
+            assert!(contract.approve(bob(), 20).is_ok());
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract
+                .retire_from(alice(), 20, String::from("Acme"))
+                .is_ok());
+            assert!(contract.transfer(charlie(), 40).is_ok());
+
+            let (total_supply, _, total_burned, total_retired) = contract.supply_breakdown();
+            assert_eq!(
+                contract.stats(),
+                TokenStats {
+                    total_supply,
+                    total_burned,
+                    total_retired,
+                    holder_count: 2,
+                    paused: false,
+                }
+            );
+            assert_eq!(contract.stats().total_supply, contract.total_supply());
+        }
+
+        #[ink::test]
+        fn retired_amounts_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(100).is_ok());
+            assert!(contract.approve(alice(), 100).is_ok());
+            assert!(contract
+                .retire_from(alice(), 10, String::from("Acme"))
+                .is_ok());
+            assert!(contract
+                .retire_from(alice(), 20, String::from("Globex"))
+                .is_ok());
+            assert!(contract
+                .retire_from(alice(), 5, String::from("Acme"))
+                .is_ok());
+            assert!(contract
+                .retire_from(alice(), 30, String::from("Initech"))
+                .is_ok());
+
+            assert_eq!(
+                contract.retired_amounts(vec![
+                    String::from("Initech"),
+                    String::from("Acme"),
+                    String::from("Globex"),
+                    String::from("Umbrella"),
+                ]),
+                vec![30, 15, 20, 0]
+            );
+        }
+
+        #[ink::test]
+        fn metadata_hash_is_stable() {
+            let mut contract = CarbonToken::default();
+            let hash = contract.metadata_hash();
+            assert_ne!(hash, [0u8; 32]);
+
+            // Balance changes do not touch the metadata
+            assert!(contract.mint(100).is_ok());
+            assert!(contract.transfer(bob(), 10).is_ok());
+            assert_eq!(contract.metadata_hash(), hash);
+
+            let other =
+                CarbonToken::with_metadata(0, String::from("Carbon"), String::from("CO2"), 18);
+            assert_ne!(other.metadata_hash(), hash);
+
+            // Tokens of different vintages are different credits
+            assert_ne!(
+                CarbonToken::with_vintage(0, 2020).metadata_hash(),
+                CarbonToken::with_vintage(0, 2021).metadata_hash()
+            );
+        }
+
+        #[ink::test]
+        fn metadata_works() {
+            let contract = CarbonToken::with_metadata(
+                100,
+                String::from("Karbonomy Carbon Credit"),
+                String::from("KCC"),
+                6,
+            );
+            assert_eq!(contract.total_supply(), 100);
+            assert_eq!(
+                contract.token_name(),
+                String::from("Karbonomy Carbon Credit")
+            );
+            assert_eq!(contract.token_symbol(), String::from("KCC"));
+            assert_eq!(contract.token_decimals(), 6);
+
+            let contract = CarbonToken::default();
+            assert_eq!(contract.token_name(), String::new());
+            assert_eq!(contract.token_decimals(), 18);
+        }
+
+        #[ink::test]
+        fn with_config_works() {
+            let project = ProjectMetadata {
+                registry: String::from("Verra"),
+                project_id: String::from("VCS-1234"),
+                methodology: String::from("VM0015"),
+                country: String::from("BR"),
+            };
+            let mut contract = CarbonToken::with_config(
+                100,
+                TokenConfig {
+                    name: String::from("Karbonomy Carbon Credit"),
+                    symbol: String::from("KCC"),
+                    decimals: 6,
+                    cap: Some(150),
+                    expiry_timestamp: Some(1_000),
+                    vintage: Some(2020),
+                    project_metadata: project.clone(),
+                },
+            );
+            assert_eq!(contract.total_supply(), 100);
+            assert_eq!(contract.token_symbol(), String::from("KCC"));
+            assert_eq!(contract.token_decimals(), 6);
+            assert_eq!(contract.cap(), Some(150));
+            assert_eq!(contract.expiry_timestamp(), Some(1_000));
+            assert_eq!(contract.vintage(), Some(2020));
+            assert_eq!(contract.project_metadata(), project);
+
+            // Every feature applies to the same token
+            assert_eq!(contract.mint(60), Err(Error::CapExceeded));
+            ink::env::test::set_block_timestamp::<Environment>(1_001);
+            assert_eq!(contract.mint(10), Err(Error::TokenExpired));
+        }
+
+        #[ink::test]
+        fn transfer_whitelist_works() {
+            let mut contract = CarbonToken::default();
+            assert!(contract.mint(100).is_ok());
+            assert!(contract.set_transfer_restricted(true).is_ok());
+            assert!(contract.is_transfer_restricted());
+
+            // Restricted phase: only transfers involving a whitelisted party
+            assert_eq!(contract.transfer(bob(), 10), Err(Error::NotAllowed));
+            assert!(contract.set_transfer_whitelisted(bob(), true).is_ok());
+            assert!(contract.transfer(bob(), 10).is_ok());
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract.transfer(charlie(), 5).is_ok());
+            ink::env::test::set_caller::<Environment>(charlie());
+            assert_eq!(contract.transfer(alice(), 5), Err(Error::NotAllowed));
+            assert_eq!(
+                contract.set_transfer_restricted(false),
+                Err(Error::NotOwner)
+            );
+
+            // Public launch: transfers are unrestricted
+            ink::env::test::set_caller::<Environment>(alice());
+            assert!(contract.set_transfer_restricted(false).is_ok());
+            ink::env::test::set_caller::<Environment>(charlie());
+            assert!(contract.transfer(alice(), 5).is_ok());
+        }
+
+        #[ink::test]
+        fn mint_overflow_fails() {
+            let mut contract = CarbonToken::new(Balance::MAX - 10);
+            assert_eq!(contract.mint(11), Err(Error::Overflow));
+            assert_eq!(contract.total_supply(), Balance::MAX - 10);
+            assert!(contract.mint(10).is_ok());
+            assert_eq!(contract.total_supply(), Balance::MAX);
+            assert_eq!(contract.mint(1), Err(Error::Overflow));
+            assert_eq!(contract.balance_of(alice()), Balance::MAX);
+        }
+
+        #[ink::test]
+        fn burn_more_than_balance_fails() {
+            let mut contract = CarbonToken::new(100);
+            assert_eq!(contract.burn(101), Err(Error::InsufficientBalance));
+            assert!(contract.burn(100).is_ok());
+            assert_eq!(contract.total_supply(), 0);
+        }
+
+        #[ink::test]
+        fn ownership_works() {
             let mut contract = CarbonToken::new(100);
+            assert_eq!(contract.owner(), alice());
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.transfer_ownership(bob()), Err(Error::NotOwner));
+
+            ink::env::test::set_caller::<Environment>(alice());
+            let events = ink::env::test::recorded_events().count();
+            assert!(contract.transfer_ownership(bob()).is_ok());
+            assert_eq!(ink::env::test::recorded_events().count(), events + 1);
+            assert_eq!(contract.owner(), bob());
+            assert_eq!(contract.grant_minter(charlie()), Err(Error::NotOwner));
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract.grant_minter(charlie()).is_ok());
+        }
+
+        #[ink::test]
+        fn minter_roles_work() {
+            let mut contract = CarbonToken::new(100);
+            assert!(contract.is_minter(alice()));
+            assert!(!contract.is_minter(bob()));
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.mint(10), Err(Error::NotAuthorized));
+            assert_eq!(contract.grant_minter(bob()), Err(Error::NotOwner));
+
+            ink::env::test::set_caller::<Environment>(alice());
+            let events = ink::env::test::recorded_events().count();
+            assert!(contract.grant_minter(bob()).is_ok());
+            assert!(contract.grant_minter(charlie()).is_ok());
+            assert_eq!(ink::env::test::recorded_events().count(), events + 2);
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract.mint(10).is_ok());
+            ink::env::test::set_caller::<Environment>(charlie());
+            assert!(contract.mint(20).is_ok());
+            assert_eq!(contract.total_supply(), 130);
+
+            ink::env::test::set_caller::<Environment>(alice());
+            assert!(contract.revoke_minter(bob()).is_ok());
+            assert!(!contract.is_minter(bob()));
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.mint(10), Err(Error::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn cap_works() {
+            let mut contract = CarbonToken::with_cap(100, 150);
+            assert_eq!(contract.cap(), Some(150));
+            assert!(contract.mint(50).is_ok());
+            assert_eq!(contract.mint(1), Err(Error::CapExceeded));
+            assert_eq!(contract.total_supply(), 150);
+
+            // Burning frees room below the cap
+            assert!(contract.burn(10).is_ok());
+            assert!(contract.mint(10).is_ok());
+
+            let mut contract = CarbonToken::new(100);
+            assert_eq!(contract.cap(), None);
+            assert!(contract.mint(1_000).is_ok());
+        }
+
+        #[ink::test]
+        fn increase_decrease_allowance_works() {
+            let mut contract = CarbonToken::new(100);
+            assert!(contract.increase_allowance(bob(), 30).is_ok());
+            assert!(contract.increase_allowance(bob(), 20).is_ok());
+            assert_eq!(contract.allowance(alice(), bob()), 50);
+
+            assert!(contract.decrease_allowance(bob(), 10).is_ok());
+            assert_eq!(contract.allowance(alice(), bob()), 40);
+            assert_eq!(
+                contract.decrease_allowance(bob(), 41),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(contract.allowance(alice(), bob()), 40);
+
+            let events = ink::env::test::recorded_events().count();
+            assert!(contract.decrease_allowance(bob(), 40).is_ok());
+            assert_eq!(ink::env::test::recorded_events().count(), events + 1);
+            assert_eq!(contract.allowance(alice(), bob()), 0);
+
+            assert!(contract.approve(bob(), Balance::MAX).is_ok());
+            assert_eq!(contract.increase_allowance(bob(), 1), Err(Error::Overflow));
+        }
+
+        #[ink::test]
+        fn burn_from_works() {
+            let mut contract = CarbonToken::new(100);
+            assert!(contract.approve(bob(), 30).is_ok());
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract.burn_from(alice(), 20).is_ok());
+            assert_eq!(contract.balance_of(alice()), 80);
+            assert_eq!(contract.total_supply(), 80);
+            assert_eq!(contract.allowance(alice(), bob()), 10);
+            assert_eq!(
+                contract.burn_from(alice(), 11),
+                Err(Error::InsufficientAllowance)
+            );
+
+            ink::env::test::set_caller::<Environment>(alice());
+            assert!(contract.approve(bob(), 1_000).is_ok());
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(
+                contract.burn_from(alice(), 81),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(contract.allowance(alice(), bob()), 1_000);
+        }
+
+        #[ink::test]
+        fn pause_works() {
+            let mut contract = CarbonToken::new(100);
+            assert!(contract.approve(bob(), 50).is_ok());
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.pause(), Err(Error::NotOwner));
+            ink::env::test::set_caller::<Environment>(alice());
+            assert!(contract.pause().is_ok());
+            assert!(contract.paused());
+            assert!(contract.stats().paused);
+
+            assert_eq!(contract.transfer(bob(), 10), Err(Error::Paused));
+            assert_eq!(contract.mint(10), Err(Error::Paused));
+            assert_eq!(contract.burn(10), Err(Error::Paused));
+            assert_eq!(
+                contract.retire(10, String::from("Acme"), String::new()),
+                Err(Error::Paused)
+            );
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(
+                contract.transfer_from(alice(), bob(), 10),
+                Err(Error::Paused)
+            );
+            assert_eq!(
+                contract.retire_from(alice(), 10, String::from("Acme")),
+                Err(Error::Paused)
+            );
             assert_eq!(contract.balance_of(alice()), 100);
+
+            ink::env::test::set_caller::<Environment>(alice());
+            assert!(contract.unpause().is_ok());
+            assert!(!contract.paused());
             assert!(contract.transfer(bob(), 10).is_ok());
-            assert_eq!(contract.balance_of(bob()), 10);
-            assert!(contract.transfer(bob(), 100).is_err());
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract.transfer_from(alice(), bob(), 10).is_ok());
+            assert_eq!(contract.balance_of(bob()), 20);
         }
 
         #[ink::test]
-        fn transfer_from_works() {
+        fn freeze_works() {
+            let mut contract = CarbonToken::new(100);
+            assert!(contract.transfer(bob(), 20).is_ok());
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.freeze(bob()), Err(Error::NotOwner));
+            ink::env::test::set_caller::<Environment>(alice());
+            let events = ink::env::test::recorded_events().count();
+            assert!(contract.freeze(bob()).is_ok());
+            assert!(contract.is_frozen(bob()));
+            assert_eq!(ink::env::test::recorded_events().count(), events + 1);
+
+            assert_eq!(contract.transfer(bob(), 10), Err(Error::AccountFrozen));
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.transfer(alice(), 10), Err(Error::AccountFrozen));
+            assert_eq!(contract.balance_of(bob()), 20);
+
+            ink::env::test::set_caller::<Environment>(alice());
+            assert_eq!(contract.unfreeze(bob()), Ok(()));
+            assert!(!contract.is_frozen(bob()));
+            assert_eq!(ink::env::test::recorded_events().count(), events + 2);
+            assert!(contract.transfer(bob(), 10).is_ok());
+            assert_eq!(contract.balance_of(bob()), 30);
+        }
+
+        /// Account id of the voucher authority key, the Blake2x256 hash of its public key.
+        const PERMIT_OWNER: [u8; 32] = [
+            0x92, 0x06, 0x98, 0x70, 0xc1, 0x85, 0xd3, 0x8a, 0xcc, 0x5b, 0xc1, 0x07, 0x68, 0xff,
+            0x56, 0x4e, 0x76, 0x76, 0x17, 0x6b, 0xa6, 0xdb, 0x19, 0xc0, 0x87, 0x53, 0xfa, 0x9a,
+            0x16, 0xbb, 0x65, 0xf1,
+        ];
+
+        /// Signature by the `PERMIT_OWNER` key over `(contract, PERMIT_OWNER, bob, 500, 0, 1_000)`
+        /// with the contract at `[0x42; 32]`.
+        const PERMIT_SIGNATURE: [u8; 65] = [
+            0x51, 0xee, 0x97, 0x04, 0x7e, 0x83, 0xe2, 0x64, 0xcb, 0xfd, 0x74, 0x1a, 0x9e, 0xaa,
+            0x3e, 0x6f, 0x25, 0x3a, 0x13, 0xc8, 0xc3, 0xb3, 0x8c, 0x55, 0xe7, 0x51, 0x14, 0xa2,
+            0xe9, 0x75, 0x2c, 0xeb, 0x56, 0xe6, 0x78, 0x7e, 0xac, 0x1f, 0xe3, 0x66, 0x57, 0xff,
+            0xa0, 0xa7, 0xd8, 0xc9, 0xad, 0x06, 0x35, 0xf1, 0xee, 0x3e, 0x76, 0x14, 0x54, 0x47,
+            0xcf, 0x5a, 0xd3, 0x7e, 0xfd, 0x5b, 0xa2, 0xf8, 0x01,
+        ];
+
+        #[ink::test]
+        fn permit_works() {
+            ink::env::test::set_callee::<Environment>(AccountId::from([0x42; 32]));
+            let mut contract = CarbonToken::default();
+            let owner = AccountId::from(PERMIT_OWNER);
+            assert_eq!(contract.nonces(owner), 0);
+
+            assert!(contract
+                .permit(owner, bob(), 500, 1_000, PERMIT_SIGNATURE)
+                .is_ok());
+            assert_eq!(contract.allowance(owner, bob()), 500);
+            assert_eq!(contract.nonces(owner), 1);
+
+            // The signature was bound to the consumed nonce
+            assert_eq!(
+                contract.permit(owner, bob(), 500, 1_000, PERMIT_SIGNATURE),
+                Err(Error::InvalidSignature)
+            );
+        }
+
+        #[ink::test]
+        fn permit_rejects_invalid_permits() {
+            ink::env::test::set_callee::<Environment>(AccountId::from([0x42; 32]));
+            let mut contract = CarbonToken::default();
+            let owner = AccountId::from(PERMIT_OWNER);
+
+            assert_eq!(
+                contract.permit(owner, bob(), 5_000, 1_000, PERMIT_SIGNATURE),
+                Err(Error::InvalidSignature)
+            );
+
+            ink::env::test::set_block_timestamp::<Environment>(1_001);
+            assert_eq!(
+                contract.permit(owner, bob(), 500, 1_000, PERMIT_SIGNATURE),
+                Err(Error::PermitExpired)
+            );
+            assert_eq!(contract.allowance(owner, bob()), 0);
+            assert_eq!(contract.nonces(owner), 0);
+        }
+
+        #[ink::test]
+        fn snapshots_work() {
+            let mut contract = CarbonToken::new(100);
+            assert_eq!(
+                contract.balance_of_at(alice(), 1),
+                Err(Error::UnknownSnapshot)
+            );
+            assert_eq!(contract.snapshot(), Ok(1));
+            assert_eq!(
+                contract.balance_of_at(alice(), 0),
+                Err(Error::UnknownSnapshot)
+            );
+
+            // Unchanged balances are read from the current state
+            assert_eq!(contract.balance_of_at(alice(), 1), Ok(100));
+            assert_eq!(contract.total_supply_at(1), Ok(100));
+
+            assert!(contract.transfer(bob(), 30).is_ok());
+            assert!(contract.transfer(bob(), 10).is_ok());
+            assert_eq!(contract.snapshot(), Ok(2));
+            assert!(contract.mint(50).is_ok());
+            assert!(contract.transfer(charlie(), 5).is_ok());
+            assert!(contract.burn(15).is_ok());
+            assert_eq!(contract.snapshot(), Ok(3));
+
+            // Later transfers do not change historical balances
+            assert!(contract.transfer(bob(), 20).is_ok());
+            assert_eq!(contract.balance_of_at(alice(), 1), Ok(100));
+            assert_eq!(contract.balance_of_at(bob(), 1), Ok(0));
+            assert_eq!(contract.balance_of_at(alice(), 2), Ok(60));
+            assert_eq!(contract.balance_of_at(bob(), 2), Ok(40));
+            assert_eq!(contract.balance_of_at(charlie(), 2), Ok(0));
+            assert_eq!(contract.balance_of_at(alice(), 3), Ok(90));
+            assert_eq!(contract.balance_of_at(bob(), 3), Ok(40));
+            assert_eq!(contract.balance_of_at(charlie(), 3), Ok(5));
+            assert_eq!(contract.balance_of(alice()), 70);
+            assert_eq!(contract.balance_of(bob()), 60);
+
+            assert_eq!(contract.total_supply_at(1), Ok(100));
+            assert_eq!(contract.total_supply_at(2), Ok(100));
+            assert_eq!(contract.total_supply_at(3), Ok(135));
+            assert_eq!(contract.total_supply_at(4), Err(Error::UnknownSnapshot));
+        }
+
+        #[ink::test]
+        fn snapshot_only_owner() {
+            let mut contract = CarbonToken::new(100);
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.snapshot(), Err(Error::NotOwner));
+            assert_eq!(contract.current_snapshot_id(), 0);
+        }
+
+        #[ink::test]
+        fn transfer_batch_works() {
+            let mut contract = CarbonToken::new(100);
+            assert!(contract
+                .transfer_batch(vec![bob(), charlie(), bob()], vec![10, 20, 5])
+                .is_ok());
+            assert_eq!(contract.balance_of(alice()), 65);
+            assert_eq!(contract.balance_of(bob()), 15);
+            assert_eq!(contract.balance_of(charlie()), 20);
+            // One Transfer event for the mint & one per recipient
+            assert_eq!(ink::env::test::recorded_events().count(), 4);
+        }
+
+        #[ink::test]
+        fn transfer_batch_fails_up_front() {
             let mut contract = CarbonToken::new(100);
+            assert_eq!(
+                contract.transfer_batch(vec![bob(), charlie()], vec![10]),
+                Err(Error::LengthMismatch)
+            );
+            assert_eq!(
+                contract.transfer_batch(vec![bob(), charlie()], vec![60, 41]),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(
+                contract.transfer_batch(vec![bob(), charlie()], vec![Balance::MAX, 1]),
+                Err(Error::Overflow)
+            );
             assert_eq!(contract.balance_of(alice()), 100);
-            let _ = contract.approve(alice(), 20);
-            let _ = contract.transfer_from(alice(), bob(), 10);
-            assert_eq!(contract.balance_of(bob()), 10);
+            assert_eq!(contract.balance_of(bob()), 0);
         }
 
         #[ink::test]
-        fn allowances_works() {
+        fn approve_batch_works() {
             let mut contract = CarbonToken::new(100);
+            assert_eq!(
+                contract.approve_batch(vec![bob(), charlie()], vec![10]),
+                Err(Error::LengthMismatch)
+            );
+            assert_eq!(contract.allowance(alice(), bob()), 0);
+
+            let events = ink::env::test::recorded_events().count();
+            assert!(contract
+                .approve_batch(vec![bob(), charlie(), alice()], vec![10, 20, 30])
+                .is_ok());
+            assert_eq!(ink::env::test::recorded_events().count(), events + 3);
+            assert_eq!(contract.allowance(alice(), bob()), 10);
+            assert_eq!(contract.allowance(alice(), charlie()), 20);
+            assert_eq!(contract.allowance(alice(), alice()), 30);
+        }
+
+        #[ink::test]
+        fn vintage_works() {
+            let contract = CarbonToken::with_vintage(100, 2020);
+            assert_eq!(contract.vintage(), Some(2020));
             assert_eq!(contract.balance_of(alice()), 100);
-            let _ = contract.approve(alice(), 200);
-            assert_eq!(contract.allowance(alice(), alice()), 200);
 
-            assert!(contract.transfer_from(alice(), bob(), 50).is_ok());
-            assert_eq!(contract.balance_of(bob()), 50);
-            assert_eq!(contract.allowance(alice(), alice()), 150);
+            assert_eq!(CarbonToken::new(100).vintage(), None);
+        }
 
-            assert!(contract.transfer_from(alice(), bob(), 100).is_err());
+        #[ink::test]
+        fn project_metadata_works() {
+            let project = ProjectMetadata {
+                registry: String::from("Verra"),
+                project_id: String::from("VCS-1234"),
+                methodology: String::from("VM0015"),
+                country: String::from("BR"),
+            };
+            let mut contract = CarbonToken::with_project_metadata(100, project.clone());
+            assert_eq!(contract.project_metadata(), project);
+            assert_eq!(contract.total_supply(), 100);
+
+            // Updating the project changes the metadata hash
+            let hash = contract.metadata_hash();
+            let updated = ProjectMetadata {
+                project_id: String::from("VCS-5678"),
+                ..project
+            };
+            assert!(contract.set_project_metadata(updated.clone()).is_ok());
+            assert_eq!(contract.project_metadata(), updated);
+            assert_ne!(contract.metadata_hash(), hash);
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(
+                contract.set_project_metadata(ProjectMetadata::default()),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(
+                CarbonToken::default().project_metadata(),
+                ProjectMetadata::default()
+            );
+        }
+
+        #[ink::test]
+        fn total_minted_and_burned_work() {
+            let mut contract = CarbonToken::new(100);
+            assert_eq!((contract.total_minted(), contract.total_burned()), (100, 0));
+
+            assert!(contract.mint(400).is_ok());
+            assert!(contract.transfer(bob(), 150).is_ok());
+            assert!(contract.burn(50).is_ok());
+            assert!(contract.grant_minter(bob()).is_ok());
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract.mint(25).is_ok());
+            assert!(contract.transfer(charlie(), 75).is_ok());
+            assert!(contract.burn(30).is_ok());
+
+            assert_eq!(contract.total_minted(), 525);
+            assert_eq!(contract.total_burned(), 80);
+            assert_eq!(
+                contract.total_minted() - contract.total_burned(),
+                contract.total_supply()
+            );
+        }
+
+        #[ink::test]
+        fn mint_to_works() {
+            let mut contract = CarbonToken::new(100);
+            let events = ink::env::test::recorded_events().count();
+            assert!(contract.mint_to(bob(), 50).is_ok());
+            // One Mint & one Transfer event
+            assert_eq!(ink::env::test::recorded_events().count(), events + 2);
+            assert_eq!(contract.balance_of(alice()), 100);
             assert_eq!(contract.balance_of(bob()), 50);
-            assert_eq!(contract.allowance(alice(), alice()), 150);
+            assert_eq!(contract.total_supply(), 150);
+            assert_eq!(contract.total_minted(), 150);
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.mint_to(bob(), 10), Err(Error::NotAuthorized));
+            assert_eq!(contract.total_supply(), 150);
+        }
+
+        #[ink::test]
+        fn vesting_works() {
+            let mut contract = CarbonToken::new(100);
+            ink::env::test::set_block_timestamp::<Environment>(1_000);
+            assert_eq!(contract.vested_amount(bob()), 0);
+
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(
+                contract.create_vesting(bob(), 1_000, 100),
+                Err(Error::NotAuthorized)
+            );
+            assert_eq!(contract.claim_vested(), Err(Error::NoVesting));
+
+            ink::env::test::set_caller::<Environment>(alice());
+            assert!(contract.create_vesting(bob(), 1_000, 100).is_ok());
+            assert_eq!(contract.total_supply(), 1_100);
+            assert_eq!(contract.balance_of(bob()), 0);
+            assert_eq!(
+                contract.vesting_schedule(bob()),
+                Some(VestingSchedule {
+                    total: 1_000,
+                    claimed: 0,
+                    start: 1_000,
+                    duration: 100,
+                })
+            );
+            assert_eq!(
+                contract.create_vesting(bob(), 1_000, 100),
+                Err(Error::VestingExists)
+            );
+
+            // Claims are not transfers, so neither taxed nor held to the transfer minimum
+            assert!(contract.set_transfer_tax(1_000, charlie()).is_ok());
+            assert!(contract.set_min_transfer_amount(500).is_ok());
+
+            // Partial unlock
+            ink::env::test::set_block_timestamp::<Environment>(1_025);
+            assert_eq!(contract.vested_amount(bob()), 250);
+            ink::env::test::set_caller::<Environment>(bob());
+            assert_eq!(contract.claim_vested(), Ok(250));
+            assert_eq!(contract.claim_vested(), Ok(0));
+            assert_eq!(contract.balance_of(bob()), 250);
+            assert_eq!(contract.balance_of(charlie()), 0);
+
+            // Full unlock
+            ink::env::test::set_block_timestamp::<Environment>(1_500);
+            assert_eq!(contract.vested_amount(bob()), 1_000);
+            assert_eq!(contract.claim_vested(), Ok(750));
+            assert_eq!(contract.balance_of(bob()), 1_000);
+            assert_eq!(contract.total_supply(), 1_100);
+
+            // A fully claimed schedule can be replaced
+            ink::env::test::set_caller::<Environment>(alice());
+            assert!(contract.create_vesting(bob(), 10, 0).is_ok());
+            assert_eq!(contract.vested_amount(bob()), 10);
+        }
+
+        #[ink::test]
+        fn safe_approve_works() {
+            let mut contract = CarbonToken::new(100);
+            assert!(contract.safe_approve(bob(), 0, 50).is_ok());
+            assert_eq!(contract.allowance(alice(), bob()), 50);
+
+            // Bob spends part of the allowance between Alice reading it & replacing it
+            let read = contract.allowance(alice(), bob());
+            ink::env::test::set_caller::<Environment>(bob());
+            assert!(contract.transfer_from(alice(), charlie(), 20).is_ok());
+            ink::env::test::set_caller::<Environment>(alice());
+
+            let events = ink::env::test::recorded_events().count();
+            assert_eq!(
+                contract.safe_approve(bob(), read, 10),
+                Err(Error::AllowanceChanged)
+            );
+            assert_eq!(contract.allowance(alice(), bob()), 30);
+            assert_eq!(ink::env::test::recorded_events().count(), events);
+
+            assert!(contract.safe_approve(bob(), 30, 10).is_ok());
+            assert_eq!(contract.allowance(alice(), bob()), 10);
+            assert_eq!(ink::env::test::recorded_events().count(), events + 1);
         }
     }
 }
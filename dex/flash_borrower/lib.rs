@@ -0,0 +1,107 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// Mock flash swap borrower used by the DEX end-to-end tests.
+#[ink::contract]
+mod flash_borrower {
+    use ink::env::call::{build_call, Call, ExecutionInput, Selector};
+
+    /// Basis points of the borrowed amount.
+    const BPS: Balance = 10_000;
+
+    /// Withdrawn `(token1, token2)` amounts, or the pool error as its SCALE index.
+    pub type WithdrawResult = Result<(Balance, Balance), u8>;
+
+    /// Borrower repaying each flash swap with a fixed premium over the borrowed amounts.
+    #[ink(storage)]
+    pub struct FlashBorrower {
+        /// Token contract backing Token1 of the pool.
+        token1: AccountId,
+        /// Token contract backing Token2 of the pool.
+        token2: AccountId,
+        /// Repaid amount, in basis points of the borrowed amount.
+        repay_bps: Balance,
+        /// Whether the handler tries to reenter the pool's `withdraw` before repaying.
+        reenter: bool,
+        /// Result of the last reentrant `withdraw`, with the pool error as its SCALE index.
+        reentry_result: Option<WithdrawResult>,
+    }
+
+    impl FlashBorrower {
+        /// Create a borrower repaying `repay_bps` basis points of every amount borrowed from a pool
+        /// trading `token1` against `token2`.
+        #[ink(constructor)]
+        pub fn new(token1: AccountId, token2: AccountId, repay_bps: Balance) -> Self {
+            Self {
+                token1,
+                token2,
+                repay_bps,
+                reenter: false,
+                reentry_result: None,
+            }
+        }
+
+        /// Create a borrower like [`FlashBorrower::new`] that also calls back into the pool's
+        /// `withdraw` from the flash swap handler.
+        #[ink(constructor)]
+        pub fn new_reentrant(token1: AccountId, token2: AccountId, repay_bps: Balance) -> Self {
+            let mut borrower = Self::new(token1, token2, repay_bps);
+            borrower.reenter = true;
+            borrower
+        }
+
+        /// Result of the last reentrant `withdraw` attempted by the handler.
+        #[ink(message)]
+        pub fn reentry_result(&self) -> Option<WithdrawResult> {
+            self.reentry_result
+        }
+
+        /// Flash swap handler called by the pool after sending the borrowed amounts.
+        ///
+        /// Approves the pool for the repaid amounts and returns them. The selector is the one the
+        /// pool calls, `ink::selector_bytes!("onFlashSwap")`.
+        #[ink(message, selector = 0xd2ffa56a)]
+        pub fn on_flash_swap(
+            &mut self,
+            _initiator: AccountId,
+            amount_token1: Balance,
+            amount_token2: Balance,
+        ) -> (Balance, Balance) {
+            let pool = self.env().caller();
+            if self.reenter {
+                self.reentry_result = Some(self.withdraw(pool, 1));
+            }
+            let repay_token1 = amount_token1 * self.repay_bps / BPS;
+            let repay_token2 = amount_token2 * self.repay_bps / BPS;
+            self.approve(self.token1, pool, repay_token1);
+            self.approve(self.token2, pool, repay_token2);
+            (repay_token1, repay_token2)
+        }
+
+        /// Withdraws `share` from the `pool` liquidity, without a deadline.
+        fn withdraw(&self, pool: AccountId, share: Balance) -> WithdrawResult {
+            build_call::<Environment>()
+                .call_type(Call::new(pool).gas_limit(0))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("withdraw")))
+                        .push_arg(share)
+                        .push_arg(u64::MAX),
+                )
+                .returns::<WithdrawResult>()
+                .invoke()
+        }
+
+        /// Approves `spender` for `value` of the `token` held by the borrower.
+        fn approve(&self, token: AccountId, spender: AccountId, value: Balance) {
+            build_call::<Environment>()
+                .call_type(Call::new(token).gas_limit(0))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("approve")))
+                        .push_arg(spender)
+                        .push_arg(value),
+                )
+                .returns::<Result<(), u8>>()
+                .invoke()
+                .expect("approve failed");
+        }
+    }
+}
@@ -2,10 +2,172 @@
 #![allow(non_snake_case)]
 
 const PRECISION: u128 = 1_000_000; // Precision of 6 digits
+const BPS: u128 = 10_000; // Basis points denominator
+const MAX_PATH_HOPS: usize = 1; // Maximum number of hops quoted by getAmountsOut, a single pool only has one
+const MAX_CHECKPOINTS: usize = 16; // Maximum number of price checkpoints kept by the pool
+const VOLATILITY_CAP_BPS: u128 = 1_000; // Price movement at which the dynamic fee reaches its maximum
+const MAX_CURVE_SAMPLES: u8 = 64; // Maximum number of points returned by sampleSwapCurve
+const MAX_VALUE_HISTORY: usize = 32; // Maximum number of position values kept per provider
+const MAX_ACTIVE_PROVIDERS: usize = 16; // Maximum number of providers sharing the rebates of a block
+const DEFAULT_EMERGENCY_TIMELOCK: u64 = 86_400_000; // Delay between an emergency request & its execution, in ms
+const FEE_GROWTH_PRECISION: u128 = 1_000_000_000_000; // Precision of the LP fees earned per share
+const MINIMUM_LIQUIDITY: u128 = 1_000; // Genesis shares locked in the pool forever
+const WAD: u128 = 1_000_000_000_000_000_000; // Precision of 18 digits used by the weighted pool math
+const LN2_WAD: i128 = 693_147_180_559_945_309; // ln(2) scaled by WAD
+const POW_ERROR_WAD: u128 = 100_000; // Upper bound of the error of powWad, scaled by WAD
+
+// (swap volume threshold, fee discount in basis points of the fee) of each fee tier
+const FEE_TIERS: [(u128, u128); 4] = [
+    (0, 0),
+    (100_000, 1_000),
+    (1_000_000, 2_500),
+    (10_000_000, 5_000),
+];
+
+// Returns ln(x) of a WAD scaled x > 0, scaled by WAD
+fn lnWad(x: u128) -> i128 {
+    // Reduces x to y * 2^k with y in [1, 2)
+    let mut y = x;
+    let mut k: i128 = 0;
+    while y >= 2 * WAD {
+        y /= 2;
+        k += 1;
+    }
+    while y < WAD {
+        y *= 2;
+        k -= 1;
+    }
+
+    // ln(y) = 2 * atanh(z) = 2 * (z + z^3 / 3 + z^5 / 5 + ...) where z = (y - 1) / (y + 1) < 1 / 3
+    let z = ((y - WAD) * WAD / (y + WAD)) as i128;
+    let zSquared = z * z / WAD as i128;
+    let mut term = z;
+    let mut sum = 0;
+    let mut n = 1;
+    while term != 0 {
+        sum += term / n;
+        term = term * zSquared / WAD as i128;
+        n += 2;
+    }
+    2 * sum + k * LN2_WAD
+}
+
+// Returns e^x of a WAD scaled x, scaled by WAD & rounded down
+// Returns None if the result does not fit in a u128
+fn expWad(x: i128) -> Option<u128> {
+    // Reduces x to k * ln(2) + r with r in [0, ln(2))
+    let k = x.div_euclid(LN2_WAD);
+    let r = x.rem_euclid(LN2_WAD) as u128;
+
+    // e^r = 1 + r + r^2 / 2! + r^3 / 3! + ...
+    let mut term = WAD;
+    let mut sum = WAD;
+    let mut n = 1;
+    while term != 0 {
+        term = term * r / WAD / n;
+        sum += term;
+        n += 1;
+    }
+
+    match k {
+        k if k <= -128 => Some(0),
+        k if k < 0 => Some(sum >> -k),
+        k if k < 128 => sum.checked_mul(1 << k),
+        _ => None,
+    }
+}
+
+// Raises a WAD scaled base to the power _numerator / _denominator, within POW_ERROR_WAD of the exact result
+// Returns None if the result does not fit in a u128
+fn powWad(base: u128, numerator: u8, denominator: u8) -> Option<u128> {
+    if base == 0 {
+        return Some(0);
+    }
+    let exponent = lnWad(base).checked_mul(numerator as i128)? / denominator as i128;
+    expWad(exponent)
+}
+
+// Returns the integer square root of a * b, without computing the product
+fn sqrtProduct(a: u128, b: u128) -> u128 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    // Newton iterations from above, a * b / x fits in a u128 as long as x >= sqrt(a * b)
+    let mut x = a.max(b);
+    loop {
+        let y = match mulDiv(a, b, x) {
+            Some(quotient) => (x + quotient) / 2,
+            None => return x,
+        };
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+// Returns a * b / denominator, computing the product on 256 bits so that it cannot overflow
+// Returns None if denominator is zero or if the result does not fit in a u128
+fn mulDiv(a: u128, b: u128, denominator: u128) -> Option<u128> {
+    mulDivRem(a, b, denominator).map(|(quotient, _)| quotient)
+}
+
+// Returns a * b / denominator rounded up, None if denominator is zero or if the result does not fit in a u128
+fn mulDivUp(a: u128, b: u128, denominator: u128) -> Option<u128> {
+    let (quotient, remainder) = mulDivRem(a, b, denominator)?;
+    if remainder == 0 {
+        Some(quotient)
+    } else {
+        quotient.checked_add(1)
+    }
+}
+
+// Returns the quotient & remainder of a * b / denominator, computing the product on 256 bits
+fn mulDivRem(a: u128, b: u128, denominator: u128) -> Option<(u128, u128)> {
+    if denominator == 0 {
+        return None;
+    }
+
+    // Builds the (high, low) 256-bit product from the 64-bit halves of both factors
+    let mask = u64::MAX as u128;
+    let (aHigh, aLow) = (a >> 64, a & mask);
+    let (bHigh, bLow) = (b >> 64, b & mask);
+    let lowLow = aLow * bLow;
+    let lowHigh = aLow * bHigh;
+    let highLow = aHigh * bLow;
+    let middle = (lowLow >> 64) + (lowHigh & mask) + (highLow & mask);
+    let low = (lowLow & mask) | (middle << 64);
+    let high = aHigh * bHigh + (lowHigh >> 64) + (highLow >> 64) + (middle >> 64);
+
+    if high == 0 {
+        return Some((low / denominator, low % denominator));
+    }
+    if high >= denominator {
+        return None;
+    }
+
+    // Long division of the 256-bit product, one bit of low at a time
+    let mut remainder = high;
+    let mut quotient = 0;
+    for i in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> i) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1;
+        }
+    }
+    Some((quotient, remainder))
+}
 
 #[ink::contract]
 mod dex {
-    use ink::storage::Mapping;
+    use ink::env::call::{build_call, Call, ExecutionInput, Selector};
+    use ink::env::CallFlags;
+    use ink::prelude::string::String;
+    use ink::prelude::vec::Vec;
+    use ink::storage::{Lazy, Mapping};
 
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
@@ -26,9 +188,207 @@ mod dex {
         InsufficientLiquidity,
         /// Slippage tolerance exceeded
         SlippageExceeded,
+        /// Caller is not the pool owner
+        NotOwner,
+        /// Swap path is empty, too long or reverses itself
+        InvalidPath,
+        /// Maximum number of liquidity providers reached
+        ProviderLimitReached,
+        /// Fee bounds are not in valid range
+        InvalidFeeBounds,
+        /// Carbon token contract address is not set
+        TokenNotSet,
+        /// Call to the carbon token contract failed
+        TokenCallFailed,
+        /// Swap recipient is on the deny list
+        ComplianceRejected,
+        /// Not enough checkpoints recorded
+        InsufficientHistory,
+        /// Previous sensitive admin action is too recent
+        AdminCooldownActive,
+        /// Recipient already received swap output within the cooldown
+        CooldownActive,
+        /// No emergency withdrawal was requested
+        EmergencyNotRequested,
+        /// Emergency withdrawal timelock has not elapsed, or is pending
+        TimelockActive,
+        /// Withdrawal would leave a position below the minimum remaining shares
+        DustPositionNotAllowed,
+        /// Call to the flash swap callback contract failed
+        CallbackFailed,
+        /// Operation is not supported by weighted pools, or by the pool's token contracts
+        UnsupportedPool,
+        /// Faucet is disabled, or Token1 & Token2 are backed by token contracts
+        FaucetDisabled,
+        /// Pool is already executing a provide, withdraw or swap
+        Reentrancy,
+        /// Pool is paused by the owner
+        Paused,
+        /// Swap is below the minimum amount, or its fee or output rounds to zero
+        AmountTooSmall,
+        /// Swap, provide or withdraw is executed after its deadline
+        DeadlineExpired,
+        /// Pools of a multi-hop swap do not share a bridge token
+        InvalidRoute,
+        /// Call to another pool contract failed
+        PoolCallFailed,
+        /// Caller is not the treasury receiving the protocol fees
+        NotTreasury,
+        /// Share allowance is less than the amount transferred
+        InsufficientAllowance,
+        /// Token contract credited the pool with less than the amount transferred
+        TokenTransferShortfall,
+    }
+
+    /// A single leg of a batch swap
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct SwapOrder {
+        pub token1In: bool,    // Sells Token1 if true, Token2 otherwise
+        pub amountIn: Balance, // Amount of the sold token
+        pub minOut: Balance,   // Minimum amount of the bought token
+    }
+
+    /// Settlement detail of an executed swap
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct SwapResult {
+        pub amountIn: Balance,       // Amount of the sold token paid by the trader
+        pub amountOut: Balance,      // Amount of the bought token sent to the recipient
+        pub feePaid: Balance,        // Swap fee charged on amountIn, in the sold token
+        pub priceImpactBps: Balance, // Change of the Token1 in Token2 spot price caused by the swap, in basis points
+    }
+
+    /// Emitted when liquidity is provided to the pool
+    #[ink(event)]
+    pub struct LiquidityAdded {
+        #[ink(topic)]
+        provider: AccountId, // Account providing the liquidity
+        amountToken1: Balance, // Amount of Token1 added to the reserves
+        amountToken2: Balance, // Amount of Token2 added to the reserves
+        shares: Balance,       // Amount of shares issued to the provider
+    }
+
+    /// Emitted when liquidity is withdrawn from the pool
+    #[ink(event)]
+    pub struct LiquidityRemoved {
+        #[ink(topic)]
+        provider: AccountId, // Account withdrawing the liquidity
+        amountToken1: Balance, // Amount of Token1 removed from the reserves
+        amountToken2: Balance, // Amount of Token2 removed from the reserves
+        shares: Balance,       // Amount of shares burned
+    }
+
+    /// Emitted when a swap is executed
+    #[ink(event)]
+    pub struct Swapped {
+        #[ink(topic)]
+        trader: AccountId, // Account executing the swap
+        token1In: bool,     // Whether Token1 was sold for Token2, or Token2 for Token1
+        amountIn: Balance,  // Amount of the sold token
+        amountOut: Balance, // Amount of the bought token
+    }
+
+    /// Emitted when a flash swap is executed
+    #[ink(event)]
+    pub struct FlashSwap {
+        #[ink(topic)]
+        trader: AccountId, // Account initiating the flash swap
+        #[ink(topic)]
+        callback: AccountId, // Contract receiving & repaying the borrowed tokens
+        amountToken1Out: Balance, // Amount of Token1 sent to the callback
+        amountToken2Out: Balance, // Amount of Token2 sent to the callback
+        amountToken1In: Balance,  // Amount of Token1 repaid by the callback
+        amountToken2In: Balance,  // Amount of Token2 repaid by the callback
+    }
+
+    /// Emitted when the owner requests an emergency withdrawal of the reserves
+    #[ink(event)]
+    pub struct EmergencyRequested {
+        #[ink(topic)]
+        owner: AccountId, // Account requesting the withdrawal
+        executableAt: Timestamp, // Earliest time at which the withdrawal can be executed
+    }
+
+    /// Emitted when the owner withdraws the reserves after the timelock
+    #[ink(event)]
+    pub struct EmergencyExecuted {
+        #[ink(topic)]
+        owner: AccountId, // Account receiving the reserves
+        amountToken1: Balance, // Amount of Token1 withdrawn
+        amountToken2: Balance, // Amount of Token2 withdrawn
+    }
+
+    /// Emitted when pool shares move between accounts
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: AccountId, // Account sending the shares
+        #[ink(topic)]
+        to: AccountId, // Account receiving the shares
+        value: Balance, // Amount of shares transferred
+    }
+
+    /// Emitted when a share holder approves a spender
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId, // Account holding the shares
+        #[ink(topic)]
+        spender: AccountId, // Account allowed to transfer the shares
+        value: Balance, // Amount of shares the spender may transfer
+    }
+
+    /// Emitted when the owner pauses trading & liquidity changes
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        owner: AccountId, // Account pausing the pool
+    }
+
+    /// Emitted when the owner resumes trading & liquidity changes
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        owner: AccountId, // Account unpausing the pool
+    }
+
+    /// Emitted when the accumulated protocol fees are sent to the treasury
+    #[ink(event)]
+    pub struct ProtocolFeesCollected {
+        #[ink(topic)]
+        treasury: AccountId, // Account receiving the fees
+        amountToken1: Balance, // Amount of Token1 collected
+        amountToken2: Balance, // Amount of Token2 collected
     }
 
-    #[derive(Default)]
+    /// Aggregated health of the pool
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct PoolStats {
+        pub totalToken1: Balance, // Amount of Token1 locked in the pool
+        pub totalToken2: Balance, // Amount of Token2 locked in the pool
+        pub totalShares: Balance, // Total amount of shares issued
+        pub tradeCount: Balance,  // Number of swaps executed by the pool
+        pub isActive: bool,       // Whether the pool holds liquidity
+        pub paused: bool,         // Whether the pool is paused
+    }
+
+    /// An account's holdings & liquidity position
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Position {
+        pub token1: Balance,             // Token1 balance of the account
+        pub token2: Balance,             // Token2 balance of the account
+        pub shares: Balance,             // Pool shares held by the account
+        pub ownershipBps: Balance,       // Shares as a fraction of totalShares, in basis points
+        pub withdrawableToken1: Balance, // Token1 released on burning all shares
+        pub withdrawableToken2: Balance, // Token2 released on burning all shares
+    }
+
+    /// (block, position value) pairs recorded for an opted-in provider
+    pub type ValueHistory = Vec<(BlockNumber, Balance)>;
+
     #[ink(storage)]
     pub struct Dex {
         totalShares: Balance, // Stores the total amount of share issued for the pool
@@ -38,29 +398,86 @@ mod dex {
         token1Balance: Mapping<AccountId, Balance>, // Stores the token1 balance of each user
         token2Balance: Mapping<AccountId, Balance>, // Stores the token2 balance of each user
         fees: Balance,        // Percent of trading fees charged on trade
+        owner: AccountId,     // Account allowed to configure the pool
+        maxProvideDeviationBps: Balance, // Max deviation of a provide's implied price from spot
+        weightToken1: u8,     // Weight of Token1 in the pool invariant
+        weightToken2: u8,     // Weight of Token2 in the pool invariant
+        minLiquidityForSwaps: Balance, // Liquidity constant the pool must exceed before swaps are enabled
+        minSwapToken1: Balance,        // Minimum amount of Token1 sold by a swap
+        minSwapToken2: Balance,        // Minimum amount of Token2 sold by a swap
+        providerCount: u32,            // Number of accounts currently holding shares
+        maxProviders: u32,             // Maximum number of accounts holding shares, 0 for unlimited
+        deployedAt: BlockNumber,       // Block at which the pool was deployed
+        tradeCount: Balance,           // Number of swaps executed by the pool
+        checkpoints: Vec<(BlockNumber, Balance, Balance)>, // Recent (block, totalToken1, totalToken2) checkpoints
+        dynamicFee: bool,   // Whether the swap fee scales with recent price movement
+        minFeeBps: Balance, // Dynamic fee charged in calm periods, in basis points
+        maxFeeBps: Balance, // Dynamic fee cap, in basis points
+        imbalanceFee: bool, // Whether the swap fee scales with the trade's price impact, takes precedence over dynamicFee
+        faucetEnabled: bool, // Whether the faucet credits free tokens, only meant for testnet deployments
+        carbonToken: Option<AccountId>, // Address of the CarbonToken contract
+        protocolFeeBps: Balance, // Share of the swap fee kept by the protocol, in basis points of the fee
+        protocolFeesToken1: Balance, // Token1 protocol fees accumulated outside the reserves
+        protocolFeesToken2: Balance, // Token2 protocol fees accumulated outside the reserves
+        treasury: AccountId,     // Account receiving the collected protocol fees
+        deniedRecipients: Mapping<AccountId, bool>, // Accounts that may not receive swap output from others
+        defaultSlippageBps: Balance, // Recommended slippage tolerance, in basis points
+        adminActionCooldown: Timestamp, // Minimum time between sensitive admin actions
+        lastAdminAction: Option<Timestamp>, // Time of the last sensitive admin action
+        targetRatio: Balance, // Target totalToken2 / totalToken1 reserve ratio, scaled by PRECISION
+        volume: Mapping<AccountId, Balance>, // Swap input volume of each user, used for fee tiers
+        quoteIsToken2: bool,  // Whether Token2 is the quote token & Token1 the base token
+        valueHistory: Mapping<AccountId, ValueHistory>, // Position values of opted-in providers
+        swapOutCooldownBlocks: BlockNumber, // Blocks between two swap outputs to the same recipient, 0 to disable
+        lastSwapOutBlock: Mapping<AccountId, BlockNumber>, // Block of the last swap output of each recipient
+        paused: bool, // Whether provides, withdraws & swaps are halted by the owner or an emergency withdrawal
+        activeProviderRebateBps: Balance, // Share of the protocol fee rebated to providers of the swap's block, 0 to disable
+        activeProvidersBlock: BlockNumber, // Block in which activeProviders provided liquidity
+        activeProviders: Vec<AccountId>, // Providers that provided liquidity in activeProvidersBlock
+        emergencyTimelock: Timestamp,    // Delay between an emergency request & its execution
+        emergencyExecutableAt: Option<Timestamp>, // Earliest execution time of the pending emergency request
+        minRemainingShares: Balance, // Minimum non-zero position left after a partial withdraw
+        principalToken1: Balance,    // Token1 deposited by providers & not yet withdrawn
+        principalToken2: Balance,    // Token2 deposited by providers & not yet withdrawn
+        token1: Option<AccountId>, // Token contract backing Token1, None to keep Token1 in the internal ledger
+        token2: Option<AccountId>, // Token contract backing Token2, None to keep Token2 in the internal ledger
+        feeGrowthToken1: Balance, // Token1 LP fees earned per share since deployment, scaled by FEE_GROWTH_PRECISION
+        feeGrowthToken2: Balance, // Token2 LP fees earned per share since deployment, scaled by FEE_GROWTH_PRECISION
+        feeGrowthPaid: Mapping<AccountId, (Balance, Balance)>, // Fee growth at each provider's last settlement
+        accruedFees: Mapping<AccountId, (Balance, Balance)>, // Token1 & Token2 LP fees settled to each provider
+        shareAllowances: Mapping<(AccountId, AccountId), Balance>, // Shares each spender may transfer for each holder
+        price1CumulativeLast: Balance, // Sum of the Token1 in Token2 spot price times the time it held, wrapping on overflow
+        price2CumulativeLast: Balance, // Sum of the Token2 in Token1 spot price times the time it held, wrapping on overflow
+        blockTimestampLast: Timestamp, // Time of the last cumulative price update
+        locked: Lazy<bool>, // Whether a provide, withdraw or swap is executing, written through so reentrant calls see it
     }
 
     #[ink(impl)]
     impl Dex {
-        // Ensures that the _qty is non-zero and the user has enough balance
-        fn validAmountCheck(
-            &self,
-            _balance: &Mapping<AccountId, Balance>,
-            _qty: Balance,
-        ) -> Result<(), Error> {
-            let caller = self.env().caller();
-            let my_balance = *_balance.get(&caller).unwrap_or(&0);
+        // Ensures that the _qty is non-zero and does not exceed the user's _balance
+        fn validAmountCheck(&self, _balance: Balance, _qty: Balance) -> Result<(), Error> {
+            match _qty {
+                0 => Err(Error::ZeroAmount),
+                _ if _qty > _balance => Err(Error::InsufficientAmount),
+                _ => Ok(()),
+            }
+        }
+
+        // Ensures that the _qty of Token1 (_token1) or Token2 is non-zero and the caller holds enough of it
+        fn validTokenAmountCheck(&self, _token1: bool, _qty: Balance) -> Result<(), Error> {
+            let balance = self.tokenBalanceOf(_token1, self.env().caller())?;
 
             match _qty {
                 0 => Err(Error::ZeroAmount),
-                _ if _qty > my_balance => Err(Error::InsufficientAmount),
+                _ if _qty > balance => Err(Error::InsufficientAmount),
                 _ => Ok(()),
             }
         }
 
-        // Returns the liquidity constant of the pool
+        // Returns the liquidity constant of the pool, saturating at u128::MAX
+        // Swap math uses mulDiv on the reserves instead, since the constant itself may not fit in a u128
         fn getK(&self) -> Balance {
-            self.totalToken1 * self.totalToken2
+            self.totalToken1.saturating_mul(self.totalToken2)
         }
 
         // Used to restrict withdraw & swap feature till liquidity is added to the pool
@@ -70,339 +487,5340 @@ mod dex {
                 _ => Ok(()),
             }
         }
-    }
 
-    impl Dex {
-        /// Constructs a new AMM instance
-        /// @param _fees: valid interval -> [0,1000)
-        #[ink(constructor)]
-        pub fn new(_fees: Balance) -> Self {
-            // Sets fees to zero if not in valid range
-            Self {
-                fees: if _fees >= 1000 { 0 } else { _fees },
-                ..Default::default()
-            }
+        // Returns true if the pool uses a weighted invariant instead of the constant product
+        fn isWeighted(&self) -> bool {
+            self.weightToken1 != self.weightToken2
         }
 
-        /// Sends free token(s) to the invoker
-        #[ink(message)]
-        pub fn faucet(&mut self, _amountToken1: Balance, _amountToken2: Balance) {
-            let caller = self.env().caller();
-            let token1 = *self.token1Balance.get(&caller).unwrap_or(&0);
-            let token2 = *self.token2Balance.get(&caller).unwrap_or(&0);
+        // Returns the output of a weighted pool swap of _amountIn, rounded down in favour of the pool
+        // amountOut = reserveOut * (1 - (reserveIn / (reserveIn + amountIn)) ^ (weightIn / weightOut))
+        fn getWeightedAmountOut(
+            &self,
+            _amountIn: Balance,
+            _reserveIn: Balance,
+            _reserveOut: Balance,
+            _weightIn: u8,
+            _weightOut: u8,
+        ) -> Result<Balance, Error> {
+            let reserveInAfter = _reserveIn
+                .checked_add(_amountIn)
+                .ok_or(Error::InsufficientLiquidity)?;
+            // The base & power are rounded up so that the complement paid out is rounded down
+            let base = super::mulDivUp(_reserveIn, super::WAD, reserveInAfter)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let ratio = super::powWad(base, _weightIn, _weightOut)
+                .and_then(|power| power.checked_add(super::POW_ERROR_WAD))
+                .ok_or(Error::InsufficientLiquidity)?;
 
-            self.token1Balance.insert(caller, token1 + _amountToken1);
-            self.token2Balance.insert(caller, token2 + _amountToken2);
-        }
+            let mut amountOut =
+                super::mulDiv(_reserveOut, super::WAD.saturating_sub(ratio), super::WAD)
+                    .ok_or(Error::InsufficientLiquidity)?;
 
-        /// Returns the balance of the user
-        #[ink(message)]
-        pub fn getMyHoldings(&self) -> (Balance, Balance, Balance) {
-            let caller = self.env().caller();
-            let token1 = *self.token1Balance.get(&caller).unwrap_or(&0);
-            let token2 = *self.token2Balance.get(&caller).unwrap_or(&0);
-            let myShares = *self.shares.get(&caller).unwrap_or(&0);
-            (token1, token2, myShares)
+            // To ensure that the pool is not completely depleted leading to inf:0 ratio
+            if amountOut >= _reserveOut {
+                amountOut = _reserveOut - 1;
+            }
+            Ok(amountOut)
         }
 
-        /// Returns the amount of tokens locked in the pool,total shares issued & trading fee param
-        #[ink(message)]
-        pub fn getPoolDetails(&self) -> (Balance, Balance, Balance, Balance) {
-            (
-                self.totalToken1,
-                self.totalToken2,
-                self.totalShares,
-                self.fees,
-            )
+        // Returns the input required by a weighted pool swap to release _amountOut, rounded up in favour of the pool
+        // amountIn = reserveIn * ((reserveOut / (reserveOut - amountOut)) ^ (weightOut / weightIn) - 1)
+        fn getWeightedAmountIn(
+            &self,
+            _amountOut: Balance,
+            _reserveIn: Balance,
+            _reserveOut: Balance,
+            _weightIn: u8,
+            _weightOut: u8,
+        ) -> Result<Balance, Error> {
+            if _amountOut >= _reserveOut {
+                return Err(Error::InsufficientLiquidity);
+            }
+
+            let base = super::mulDivUp(_reserveOut, super::WAD, _reserveOut - _amountOut)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let ratio = super::powWad(base, _weightOut, _weightIn)
+                .and_then(|power| power.checked_add(super::POW_ERROR_WAD))
+                .ok_or(Error::InsufficientLiquidity)?;
+
+            super::mulDivUp(_reserveIn, ratio.saturating_sub(super::WAD), super::WAD)
+                .ok_or(Error::InsufficientLiquidity)
         }
 
-        /// Returns amount of Token1 required when providing liquidity with _amountToken2 quantity of Token2
-        #[ink(message)]
-        pub fn getEquivalentToken1Estimate(
+        // Returns the fee adjusted output of swapping _amountIn against the given reserves
+        fn getAmountOutForReserves(
             &self,
-            _amountToken2: Balance,
+            _amountIn: Balance,
+            _reserveIn: Balance,
+            _reserveOut: Balance,
+            _token1In: bool,
         ) -> Result<Balance, Error> {
-            self.activePool()?;
-            Ok(self.totalToken1 * _amountToken2 / self.totalToken2)
+            // Adjusting the fees charged
+            let feeBps = self.getFeeBpsForAmountIn(_reserveIn, _amountIn);
+            let _amountIn = super::mulDiv(_amountIn, super::BPS - feeBps, super::BPS)
+                .ok_or(Error::InsufficientLiquidity)?;
+
+            if self.isWeighted() {
+                let (weightIn, weightOut) = if _token1In {
+                    (self.weightToken1, self.weightToken2)
+                } else {
+                    (self.weightToken2, self.weightToken1)
+                };
+                return self.getWeightedAmountOut(
+                    _amountIn,
+                    _reserveIn,
+                    _reserveOut,
+                    weightIn,
+                    weightOut,
+                );
+            }
+
+            let reserveInAfter = _reserveIn
+                .checked_add(_amountIn)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let reserveOutAfter = super::mulDiv(_reserveIn, _reserveOut, reserveInAfter)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let mut amountOut = _reserveOut - reserveOutAfter;
+
+            // To ensure that the pool is not completely depleted leading to inf:0 ratio
+            if amountOut == _reserveOut {
+                amountOut -= 1;
+            }
+            Ok(amountOut)
         }
 
-        /// Returns amount of Token2 required when providing liquidity with _amountToken1 quantity of Token1
-        #[ink(message)]
-        pub fn getEquivalentToken2Estimate(
+        // Returns the part of _reserve owed to the shares, i.e. the smallest reserve that keeps the
+        // PRECISION scaled value of a share unchanged: ceil(floor(reserve * PRECISION / totalShares) * totalShares / PRECISION)
+        fn getOwedReserve(&self, _reserve: Balance) -> Balance {
+            let shareValue = _reserve * super::PRECISION / self.totalShares;
+            (shareValue * self.totalShares).div_ceil(super::PRECISION)
+        }
+
+        // Rejects a swap selling _amountIn of Token1 (_token1In) or Token2 for _amountOut at _feeBps if it sells
+        // less than the minimum swap amount, or if its output or non-zero fee rate rounds to nothing
+        fn dustSwapCheck(
             &self,
-            _amountToken1: Balance,
-        ) -> Result<Balance, Error> {
+            _token1In: bool,
+            _amountIn: Balance,
+            _amountOut: Balance,
+            _feeBps: Balance,
+        ) -> Result<(), Error> {
+            let minSwap = if _token1In {
+                self.minSwapToken1
+            } else {
+                self.minSwapToken2
+            };
+            if _amountIn < minSwap
+                || _amountOut == 0
+                || (_feeBps != 0 && _amountIn * _feeBps / super::BPS == 0)
+            {
+                return Err(Error::AmountTooSmall);
+            }
+            Ok(())
+        }
+
+        // Used to halt provides, withdraws & swaps while the pool is paused
+        fn notPausedCheck(&self) -> Result<(), Error> {
+            if self.paused {
+                return Err(Error::Paused);
+            }
+            Ok(())
+        }
+
+        // Used to reject swaps, provides & withdraws executed after the caller's _deadline
+        fn deadlineCheck(&self, _deadline: u64) -> Result<(), Error> {
+            if self.env().block_timestamp() > _deadline {
+                return Err(Error::DeadlineExpired);
+            }
+            Ok(())
+        }
+
+        // Used to restrict swaps till the liquidity constant exceeds minLiquidityForSwaps
+        fn swapEnabledCheck(&self) -> Result<(), Error> {
+            self.notPausedCheck()?;
             self.activePool()?;
-            Ok(self.totalToken2 * _amountToken1 / self.totalToken1)
+            if self.getK() <= self.minLiquidityForSwaps {
+                return Err(Error::InsufficientLiquidity);
+            }
+            Ok(())
         }
 
-        /// Adding new liquidity in the pool
-        /// Returns the amount of share issued for locking given assets
-        #[ink(message)]
-        pub fn provide(
+        // Runs _action holding the reentrancy lock, which is released on every return path
+        // Fails with Reentrancy if the lock is already held by an outer provide, withdraw or swap
+        fn nonReentrant<T>(
             &mut self,
-            _amountToken1: Balance,
-            _amountToken2: Balance,
-        ) -> Result<Balance, Error> {
-            self.validAmountCheck(&self.token1Balance, _amountToken1)?;
-            self.validAmountCheck(&self.token2Balance, _amountToken2)?;
+            _action: impl FnOnce(&mut Self) -> Result<T, Error>,
+        ) -> Result<T, Error> {
+            if self.locked.get().unwrap_or(false) {
+                return Err(Error::Reentrancy);
+            }
+            self.locked.set(&true);
+            let result = _action(self);
+            self.locked.set(&false);
+            result
+        }
 
-            let share;
-            if self.totalShares == 0 {
-                // Genesis liquidity is issued 100 Shares
-                share = 100 * super::PRECISION;
+        // Returns the swap fee charged to the caller in basis points for a trade of _impactBps price impact,
+        // after its fee tier discount
+        fn getFeeBps(&self, _impactBps: Balance) -> Balance {
+            let (_, feeBps) = self.getFeeTier(self.env().caller(), _impactBps);
+            feeBps
+        }
+
+        // Returns the swap fee charged to the caller in basis points for selling _amountIn against _reserveIn
+        fn getFeeBpsForAmountIn(&self, _reserveIn: Balance, _amountIn: Balance) -> Balance {
+            let impactBps =
+                Self::getPriceImpactBps(_reserveIn, _reserveIn.saturating_add(_amountIn));
+            self.getFeeBps(impactBps)
+        }
+
+        // Returns the swap fee charged to the caller in basis points for buying _amountOut from _reserveOut
+        fn getFeeBpsForAmountOut(&self, _reserveOut: Balance, _amountOut: Balance) -> Balance {
+            let impactBps =
+                Self::getPriceImpactBps(_reserveOut, _reserveOut.saturating_sub(_amountOut));
+            self.getFeeBps(impactBps)
+        }
+
+        // Returns how far a constant product trade moving one reserve from _reserveBefore to _reserveAfter moves
+        // the reserve ratio, in basis points: BPS - BPS * (low / high)^2 where low & high are the smaller &
+        // larger of the two reserves, since the ratio scales with the square of either reserve's change
+        fn getPriceImpactBps(_reserveBefore: Balance, _reserveAfter: Balance) -> Balance {
+            let (low, high) = if _reserveBefore < _reserveAfter {
+                (_reserveBefore, _reserveAfter)
             } else {
-                let share1 = self.totalShares * _amountToken1 / self.totalToken1;
-                let share2 = self.totalShares * _amountToken2 / self.totalToken2;
+                (_reserveAfter, _reserveBefore)
+            };
+            if high == 0 {
+                return 0;
+            }
+            // low <= high, so the scaled ratio never exceeds BPS
+            let ratio = super::mulDiv(low, super::BPS, high).unwrap_or(0);
+            super::BPS - ratio * ratio / super::BPS
+        }
 
-                if share1 != share2 {
-                    return Err(Error::NonEquivalentValue);
+        // Returns the fee tier of _user and the swap fee it is charged in basis points for a trade of
+        // _impactBps price impact
+        fn getFeeTier(&self, _user: AccountId, _impactBps: Balance) -> (u8, Balance) {
+            let userVolume = self.volume.get(_user).unwrap_or(0);
+            let tier = super::FEE_TIERS
+                .iter()
+                .rposition(|(threshold, _)| userVolume >= *threshold)
+                .unwrap_or(0);
+            let (_, discount) = super::FEE_TIERS[tier];
+            (
+                tier as u8,
+                self.getBaseFeeBps(_impactBps) * (super::BPS - discount) / super::BPS,
+            )
+        }
+
+        // Returns the swap fee in basis points before fee tier discounts for a trade of _impactBps price impact
+        // With the imbalance fee enabled: minFeeBps + (maxFeeBps - minFeeBps) * min(_impactBps, VOLATILITY_CAP_BPS) / VOLATILITY_CAP_BPS
+        // With the dynamic fee enabled: minFeeBps + (maxFeeBps - minFeeBps) * min(movement, VOLATILITY_CAP_BPS) / VOLATILITY_CAP_BPS
+        // where movement is the price change between the two most recent checkpoints in basis points
+        fn getBaseFeeBps(&self, _impactBps: Balance) -> Balance {
+            if self.imbalanceFee {
+                let impact = _impactBps.min(super::VOLATILITY_CAP_BPS);
+                return self.minFeeBps
+                    + (self.maxFeeBps - self.minFeeBps) * impact / super::VOLATILITY_CAP_BPS;
+            }
+            if !self.dynamicFee {
+                return self.fees * super::BPS / 1000;
+            }
+
+            let movement = self.getPriceMovementBps().min(super::VOLATILITY_CAP_BPS);
+            self.minFeeBps
+                + (self.maxFeeBps - self.minFeeBps) * movement / super::VOLATILITY_CAP_BPS
+        }
+
+        // Returns the price change between the two most recent checkpoints in basis points
+        fn getPriceMovementBps(&self) -> Balance {
+            let count = self.checkpoints.len();
+            if count < 2 {
+                return 0;
+            }
+
+            let (_, previous1, previous2) = self.checkpoints[count - 2];
+            let (_, latest1, latest2) = self.checkpoints[count - 1];
+            if previous1 == 0 || previous2 == 0 || latest1 == 0 {
+                return 0;
+            }
+
+            let previousPrice = previous2 * super::PRECISION / previous1;
+            let latestPrice = latest2 * super::PRECISION / latest1;
+            latestPrice.abs_diff(previousPrice) * super::BPS / previousPrice
+        }
+
+        // Records the current reserves, replacing the checkpoint of the current block if any
+        fn recordCheckpoint(&mut self) {
+            let block = self.env().block_number();
+            match self.checkpoints.last() {
+                Some((lastBlock, _, _)) if *lastBlock == block => {
+                    self.checkpoints.pop();
+                }
+                _ if self.checkpoints.len() >= super::MAX_CHECKPOINTS => {
+                    self.checkpoints.remove(0);
                 }
-                share = share1;
+                _ => {}
             }
+            self.checkpoints
+                .push((block, self.totalToken1, self.totalToken2));
+        }
 
-            if share == 0 {
-                return Err(Error::ThresholdNotReached);
+        // Returns the spot price of Token1 in Token2 if _token1InToken2 is true & of Token2 in Token1 otherwise
+        // scaled by PRECISION, or None if the pool is empty or the price does not fit in a Balance
+        fn getSpotPrice(&self, _token1InToken2: bool) -> Option<Balance> {
+            let (reserveIn, weightIn, reserveOut, weightOut) = if _token1InToken2 {
+                (
+                    self.totalToken1,
+                    self.weightToken1,
+                    self.totalToken2,
+                    self.weightToken2,
+                )
+            } else {
+                (
+                    self.totalToken2,
+                    self.weightToken2,
+                    self.totalToken1,
+                    self.weightToken1,
+                )
+            };
+            if reserveIn == 0 {
+                return None;
             }
+            let price = super::mulDiv(reserveOut, super::PRECISION, reserveIn)?;
+            super::mulDiv(price, weightIn as Balance, weightOut as Balance)
+        }
 
-            let caller = self.env().caller();
-            let token1 = *self.token1Balance.get(&caller).unwrap();
-            let token2 = *self.token2Balance.get(&caller).unwrap();
-            self.token1Balance.insert(caller, token1 - _amountToken1);
-            self.token2Balance.insert(caller, token2 - _amountToken2);
+        // Accumulates the spot prices held since the last update, weighted by the elapsed time
+        // Must run before the reserves change so that the accumulators see the previous prices
+        fn updateCumulativePrices(&mut self) {
+            let now = self.env().block_timestamp();
+            let elapsed = now.saturating_sub(self.blockTimestampLast) as Balance;
+            if elapsed > 0 {
+                if let (Some(price1), Some(price2)) =
+                    (self.getSpotPrice(true), self.getSpotPrice(false))
+                {
+                    self.price1CumulativeLast = self
+                        .price1CumulativeLast
+                        .wrapping_add(price1.wrapping_mul(elapsed));
+                    self.price2CumulativeLast = self
+                        .price2CumulativeLast
+                        .wrapping_add(price2.wrapping_mul(elapsed));
+                }
+            }
+            self.blockTimestampLast = now;
+        }
 
-            self.totalToken1 += _amountToken1;
-            self.totalToken2 += _amountToken2;
-            self.totalShares += share;
-            self.shares
-                .entry(caller)
-                .and_modify(|val| *val += share)
-                .or_insert(share);
+        // Returns the balance of _owner on the _token contract through a cross-contract call
+        fn queryTokenBalance(
+            &self,
+            _token: AccountId,
+            _owner: AccountId,
+        ) -> Result<Balance, Error> {
+            build_call::<Environment>()
+                .call_type(Call::new(_token).gas_limit(0))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("balance_of")))
+                        .push_arg(_owner),
+                )
+                .returns::<Balance>()
+                .try_invoke()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)
+        }
 
-            Ok(share)
+        // Returns the CarbonToken balance of _owner through a cross-contract call
+        fn getCarbonTokenBalance(&self, _owner: AccountId) -> Result<Balance, Error> {
+            let token = self.carbonToken.ok_or(Error::TokenNotSet)?;
+            self.queryTokenBalance(token, _owner)
         }
 
-        /// Returns the estimate of Token1 & Token2 that will be released on burning given _share
-        #[ink(message)]
-        pub fn getWithdrawEstimate(&self, _share: Balance) -> Result<(Balance, Balance), Error> {
-            self.activePool()?;
-            if _share > self.totalShares {
-                return Err(Error::InvalidShare);
+        // Returns the contract backing Token1 (_token1) or Token2, None if it is kept in the internal ledger
+        fn tokenContract(&self, _token1: bool) -> Option<AccountId> {
+            if _token1 {
+                self.token1
+            } else {
+                self.token2
             }
+        }
 
-            let amountToken1 = _share * self.totalToken1 / self.totalShares;
-            let amountToken2 = _share * self.totalToken2 / self.totalShares;
-            Ok((amountToken1, amountToken2))
+        // Returns the Token1 (_token1) or Token2 balance of _owner outside the pool
+        fn tokenBalanceOf(&self, _token1: bool, _owner: AccountId) -> Result<Balance, Error> {
+            match self.tokenContract(_token1) {
+                Some(token) => self.queryTokenBalance(token, _owner),
+                None => Ok(self.ledgerBalanceOf(_token1, _owner)),
+            }
         }
 
-        /// Removes liquidity from the pool and releases corresponding Token1 & Token2 to the withdrawer
-        #[ink(message)]
-        pub fn withdraw(&mut self, _share: Balance) -> Result<(Balance, Balance), Error> {
-            let caller = self.env().caller();
-            self.validAmountCheck(&self.shares, _share)?;
+        // Returns the internal ledger Token1 (_token1) or Token2 balance of _owner
+        // Both ledgers are kept in separate fields, as each storage field has its own key type
+        fn ledgerBalanceOf(&self, _token1: bool, _owner: AccountId) -> Balance {
+            if _token1 {
+                self.token1Balance.get(_owner).unwrap_or(0)
+            } else {
+                self.token2Balance.get(_owner).unwrap_or(0)
+            }
+        }
 
-            let (amountToken1, amountToken2) = self.getWithdrawEstimate(_share)?;
-            self.shares.entry(caller).and_modify(|val| *val -= _share);
-            self.totalShares -= _share;
+        // Sets the internal ledger Token1 (_token1) or Token2 balance of _owner
+        fn setLedgerBalance(&mut self, _token1: bool, _owner: AccountId, _balance: Balance) {
+            if _token1 {
+                self.token1Balance.insert(_owner, &_balance);
+            } else {
+                self.token2Balance.insert(_owner, &_balance);
+            }
+        }
 
-            self.totalToken1 -= amountToken1;
-            self.totalToken2 -= amountToken2;
+        // Moves _amount of Token1 (_token1) or Token2 from _from into the pool
+        // Token contracts require _from to have approved the pool for at least _amount
+        // Fails if the token contract credits the pool less than _amount, e.g. after a transfer tax
+        fn pullToken(
+            &mut self,
+            _token1: bool,
+            _from: AccountId,
+            _amount: Balance,
+        ) -> Result<(), Error> {
+            if let Some(token) = self.tokenContract(_token1) {
+                let pool = self.env().account_id();
+                let balanceBefore = self.queryTokenBalance(token, pool)?;
+                self.transferTokenFrom(token, _from, pool, _amount)?;
+                let balanceAfter = self.queryTokenBalance(token, pool)?;
+                if balanceAfter.saturating_sub(balanceBefore) != _amount {
+                    return Err(Error::TokenTransferShortfall);
+                }
+                return Ok(());
+            }
 
-            self.token1Balance
-                .entry(caller)
-                .and_modify(|val| *val += amountToken1);
-            self.token2Balance
-                .entry(caller)
-                .and_modify(|val| *val += amountToken2);
+            let fromBalance = self.ledgerBalanceOf(_token1, _from);
+            if fromBalance < _amount {
+                return Err(Error::InsufficientAmount);
+            }
+            self.setLedgerBalance(_token1, _from, fromBalance - _amount);
+            Ok(())
+        }
 
-            Ok((amountToken1, amountToken2))
+        // Moves _amount of the _token contract from _from to _to, _from must have approved this contract
+        fn transferTokenFrom(
+            &self,
+            _token: AccountId,
+            _from: AccountId,
+            _to: AccountId,
+            _amount: Balance,
+        ) -> Result<(), Error> {
+            build_call::<Environment>()
+                .call_type(Call::new(_token).gas_limit(0))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("transfer_from")))
+                        .push_arg(_from)
+                        .push_arg(_to)
+                        .push_arg(_amount),
+                )
+                // CarbonToken errors carry no data, so they decode as their variant index
+                .returns::<Result<(), u8>>()
+                .try_invoke()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)
+        }
+
+        // Approves _spender for _amount of the _token contract held by this contract
+        fn approveToken(
+            &self,
+            _token: AccountId,
+            _spender: AccountId,
+            _amount: Balance,
+        ) -> Result<(), Error> {
+            build_call::<Environment>()
+                .call_type(Call::new(_token).gas_limit(0))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("approve")))
+                        .push_arg(_spender)
+                        .push_arg(_amount),
+                )
+                .returns::<Result<(), u8>>()
+                .try_invoke()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)
+        }
+
+        // Sends _amount of Token1 (_token1) or Token2 from the pool to _to
+        fn pushToken(
+            &mut self,
+            _token1: bool,
+            _to: AccountId,
+            _amount: Balance,
+        ) -> Result<(), Error> {
+            if let Some(token) = self.tokenContract(_token1) {
+                return build_call::<Environment>()
+                    .call_type(Call::new(token).gas_limit(0))
+                    .exec_input(
+                        ExecutionInput::new(Selector::new(ink::selector_bytes!("transfer")))
+                            .push_arg(_to)
+                            .push_arg(_amount),
+                    )
+                    .returns::<Result<(), u8>>()
+                    .try_invoke()
+                    .map_err(|_| Error::TokenCallFailed)?
+                    .map_err(|_| Error::TokenCallFailed)?
+                    .map_err(|_| Error::TokenCallFailed);
+            }
+
+            let toBalance = self.ledgerBalanceOf(_token1, _to);
+            self.setLedgerBalance(_token1, _to, toBalance + _amount);
+            Ok(())
+        }
+
+        // Returns the settlement detail of a swap of _amountIn for _amountOut charged _feeBps, executed when
+        // the Token1 in Token2 spot price was _spotBefore. Must be called after the reserves are updated
+        fn getSwapResult(
+            &self,
+            _amountIn: Balance,
+            _amountOut: Balance,
+            _feeBps: Balance,
+            _spotBefore: Option<Balance>,
+        ) -> SwapResult {
+            let priceImpactBps = match (_spotBefore, self.getSpotPrice(true)) {
+                (Some(before), Some(after)) if before != 0 => {
+                    super::mulDiv(before.abs_diff(after), super::BPS, before)
+                        .unwrap_or(Balance::MAX)
+                }
+                _ => 0,
+            };
+            SwapResult {
+                amountIn: _amountIn,
+                amountOut: _amountOut,
+                feePaid: _amountIn * _feeBps / super::BPS,
+                priceImpactBps,
+            }
+        }
+
+        // Calls the onFlashSwap handler of _callback & returns the Token1 & Token2 amounts it repays
+        fn callFlashSwapHandler(
+            &self,
+            _callback: AccountId,
+            _amountToken1Out: Balance,
+            _amountToken2Out: Balance,
+        ) -> Result<(Balance, Balance), Error> {
+            build_call::<Environment>()
+                .call_type(Call::new(_callback).gas_limit(0))
+                // Lets the handler query the pool, provides, withdraws & swaps fail with Reentrancy
+                .call_flags(CallFlags::default().set_allow_reentry(true))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("onFlashSwap")))
+                        .push_arg(self.env().caller())
+                        .push_arg(_amountToken1Out)
+                        .push_arg(_amountToken2Out),
+                )
+                .returns::<(Balance, Balance)>()
+                .try_invoke()
+                .map_err(|_| Error::CallbackFailed)?
+                .map_err(|_| Error::CallbackFailed)
         }
-    }
 
-    /// Returns the amount of Token2 that the user will get when swapping a given amount of Token1 for Token2
-    #[ink(message)]
-    pub fn getSwapToken1EstimateGivenToken1(
-        &self,
-        _amountToken1: Balance,
-    ) -> Result<Balance, Error> {
-        self.activePool()?;
-        let _amountToken1 = (1000 - self.fees) * _amountToken1 / 1000; // Adjusting the fees charged
-
-        let token1After = self.totalToken1 + _amountToken1;
-        let token2After = self.getK() / token1After;
-        let mut amountToken2 = self.totalToken2 - token2After;
+        // Returns the token contracts backing Token1 & Token2 of _pool through a cross-contract call
+        fn queryPoolTokens(
+            &self,
+            _pool: AccountId,
+        ) -> Result<(Option<AccountId>, Option<AccountId>), Error> {
+            build_call::<Environment>()
+                .call_type(Call::new(_pool).gas_limit(0))
+                .exec_input(ExecutionInput::new(Selector::new(ink::selector_bytes!(
+                    "getTokens"
+                ))))
+                .returns::<(Option<AccountId>, Option<AccountId>)>()
+                .try_invoke()
+                .map_err(|_| Error::PoolCallFailed)?
+                .map_err(|_| Error::PoolCallFailed)
+        }
 
-        // To ensure that Token2's pool is not completely depleted leading to inf:0 ratio
-        if amountToken2 == self.totalToken2 {
-            amountToken2 -= 1;
-        }
-        Ok(amountToken2)
-    }
-
-    /// Returns the amount of Token1 that the user should swap to get _amountToken2 in return
-    #[ink(message)]
-    pub fn getSwapToken1EstimateGivenToken2(
-        &self,
-        _amountToken2: Balance,
-    ) -> Result<Balance, Error> {
-        self.activePool()?;
-        if _amountToken2 >= self.totalToken2 {
-            return Err(Error::InsufficientLiquidity);
+        // Swaps _amountIn of Token1 (_token1In) or Token2 on _pool through a cross-contract call & sends the
+        // output to _to. Errors of _pool are passed through unchanged
+        fn callPoolSwap(
+            &self,
+            _pool: AccountId,
+            _token1In: bool,
+            _amountIn: Balance,
+            _minOut: Balance,
+            _to: AccountId,
+            _deadline: u64,
+        ) -> Result<SwapResult, Error> {
+            build_call::<Environment>()
+                .call_type(Call::new(_pool).gas_limit(0))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("swapTo")))
+                        .push_arg(_token1In)
+                        .push_arg(_amountIn)
+                        .push_arg(_minOut)
+                        .push_arg(_to)
+                        .push_arg(_deadline),
+                )
+                .returns::<Result<SwapResult, Error>>()
+                .try_invoke()
+                .map_err(|_| Error::PoolCallFailed)?
+                .map_err(|_| Error::PoolCallFailed)?
         }
 
-        let token2After = self.totalToken2 - _amountToken2;
-        let token1After = self.getK() / token2After;
-        let amountToken1 = (token1After - self.totalToken1) * 1000 / (1000 - self.fees);
-        Ok(amountToken1)
-    }
-
-    /// Swaps given amount of Token1 to Token2 using algorithmic price determination
-    /// Swap fails if Token2 amount is less than _minToken2
-    #[ink(message)]
-    pub fn swapToken1GivenToken1(
-        &mut self,
-        _amountToken1: Balance,
-        _minToken2: Balance,
-    ) -> Result<Balance, Error> {
-        let caller = self.env().caller();
-        self.validAmountCheck(&self.token1Balance, _amountToken1)?;
-
-        let amountToken2 = self.getSwapToken1EstimateGivenToken1(_amountToken1)?;
-        if amountToken2 < _minToken2 {
-            return Err(Error::SlippageExceeded);
-        }
-        self.token1Balance
-            .entry(caller)
-            .and_modify(|val| *val -= _amountToken1);
-
-        self.totalToken1 += _amountToken1;
-        self.totalToken2 -= amountToken2;
-
-        self.token2Balance
-            .entry(caller)
-            .and_modify(|val| *val += amountToken2);
-        Ok(amountToken2)
-    }
-
-    /// Swaps given amount of Token1 to Token2 using algorithmic price determination
-    /// Swap fails if amount of Token1 required to obtain _amountToken2 exceeds _maxToken1
-    #[ink(message)]
-    pub fn swapToken1GivenToken2(
-        &mut self,
-        _amountToken2: Balance,
-        _maxToken1: Balance,
-    ) -> Result<Balance, Error> {
-        let caller = self.env().caller();
-        let amountToken1 = self.getSwapToken1EstimateGivenToken2(_amountToken2)?;
-        if amountToken1 > _maxToken1 {
-            return Err(Error::SlippageExceeded);
-        }
-        self.validAmountCheck(&self.token1Balance, amountToken1)?;
-
-        self.token1Balance
-            .entry(caller)
-            .and_modify(|val| *val -= amountToken1);
-
-        self.totalToken1 += amountToken1;
-        self.totalToken2 -= _amountToken2;
-
-        self.token2Balance
-            .entry(caller)
-            .and_modify(|val| *val += _amountToken2);
-        Ok(amountToken1)
-    }
-
-    /// Returns the amount of Token2 that the user will get when swapping a given amount of Token1 for Token2
-    #[ink(message)]
-    pub fn getSwapToken2EstimateGivenToken2(
-        &self,
-        _amountToken2: Balance,
-    ) -> Result<Balance, Error> {
-        self.activePool()?;
-        let _amountToken2 = (1000 - self.fees) * _amountToken2 / 1000; // Adjusting the fees charged
-
-        let token2After = self.totalToken2 + _amountToken2;
-        let token1After = self.getK() / token2After;
-        let mut amountToken1 = self.totalToken1 - token1After;
-
-        // To ensure that Token1's pool is not completely depleted leading to inf:0 ratio
-        if amountToken1 == self.totalToken1 {
-            amountToken1 -= 1;
-        }
-        Ok(amountToken1)
-    }
-
-    /// Returns the amount of Token2 that the user should swap to get _amountToken1 in return
-    #[ink(message)]
-    pub fn getSwapToken2EstimateGivenToken1(
-        &self,
-        _amountToken1: Balance,
-    ) -> Result<Balance, Error> {
-        self.activePool()?;
-        if _amountToken1 >= self.totalToken1 {
-            return Err(Error::InsufficientLiquidity);
-        }
-
-        let token1After = self.totalToken1 - _amountToken1;
-        let token2After = self.getK() / token1After;
-        let amountToken2 = (token2After - self.totalToken2) * 1000 / (1000 - self.fees);
-        Ok(amountToken2)
-    }
-
-    /// Swaps given amount of Token2 to Token1 using algorithmic price determination
-    /// Swap fails if Token1 amount is less than _minToken1
-    #[ink(message)]
-    pub fn swapToken2GivenToken2(
-        &mut self,
-        _amountToken2: Balance,
-        _minToken1: Balance,
-    ) -> Result<Balance, Error> {
-        let caller = self.env().caller();
-        self.validAmountCheck(&self.token2Balance, _amountToken2)?;
-
-        let amountToken1 = self.getSwapToken2EstimateGivenToken2(_amountToken2)?;
-        if amountToken1 < _minToken1 {
-            return Err(Error::SlippageExceeded);
-        }
-        self.token2Balance
-            .entry(caller)
-            .and_modify(|val| *val -= _amountToken2);
-
-        self.totalToken2 += _amountToken2;
-        self.totalToken1 -= amountToken1;
-
-        self.token1Balance
-            .entry(caller)
-            .and_modify(|val| *val += amountToken1);
-        Ok(amountToken1)
-    }
-
-    /// Swaps given amount of Token2 to Token1 using algorithmic price determination
-    /// Swap fails if amount of Token2 required to obtain _amountToken1 exceeds _maxToken2
-    #[ink(message)]
-    pub fn swapToken2GivenToken1(
-        &mut self,
-        _amountToken1: Balance,
-        _maxToken2: Balance,
-    ) -> Result<Balance, Error> {
-        let caller = self.env().caller();
-
-        let amountToken2 = self.getSwapToken2EstimateGivenToken1(_amountToken1)?;
-        if amountToken2 > _maxToken2 {
-            return Err(Error::SlippageExceeded);
-        }
-        self.validAmountCheck(&self.token2Balance, amountToken2)?;
-
-        self.token2Balance
-            .entry(caller)
-            .and_modify(|val| *val -= amountToken2);
-
-        self.totalToken2 += amountToken2;
-        self.totalToken1 -= _amountToken1;
-
-        self.token1Balance
-            .entry(caller)
-            .and_modify(|val| *val += _amountToken1);
-        Ok(amountToken2)
+        // Retires _amount of the CarbonTokens held by the pool contract
+        fn retireCarbonTokens(&self, _amount: Balance) -> Result<(), Error> {
+            let token = self.carbonToken.ok_or(Error::TokenNotSet)?;
+            build_call::<Environment>()
+                .call_type(Call::new(token).gas_limit(0))
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("retire")))
+                        .push_arg(_amount)
+                        .push_arg(String::from("Karbonomy DEX"))
+                        .push_arg(String::from("Protocol fee buyback")),
+                )
+                // CarbonToken errors carry no data, so they decode as their variant index
+                .returns::<Result<u128, u8>>()
+                .try_invoke()
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)?
+                .map_err(|_| Error::TokenCallFailed)?;
+            Ok(())
+        }
+
+        // Adds _amountIn to the swap volume of the caller
+        fn recordVolume(&mut self, _amountIn: Balance) {
+            let caller = self.env().caller();
+            let userVolume = self.volume.get(caller).unwrap_or(0);
+            self.volume.insert(caller, &(userVolume + _amountIn));
+        }
+
+        // Moves the protocol share of the _feeBps fee charged on _amountIn out of the swap input
+        // Returns the part of _amountIn that is added to the reserves
+        fn takeProtocolFee(
+            &mut self,
+            _amountIn: Balance,
+            _token1In: bool,
+            _feeBps: Balance,
+        ) -> Result<Balance, Error> {
+            let fee = super::mulDiv(_amountIn, _feeBps, super::BPS)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let protocolFee = super::mulDiv(fee, self.protocolFeeBps, super::BPS)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let feeGrowth = match self.totalShares {
+                0 => 0,
+                totalShares => {
+                    super::mulDiv(fee - protocolFee, super::FEE_GROWTH_PRECISION, totalShares)
+                        .ok_or(Error::InsufficientLiquidity)?
+                }
+            };
+            let rebate = self.payActiveProviderRebate(protocolFee, _token1In);
+            if _token1In {
+                self.protocolFeesToken1 += protocolFee - rebate;
+                self.feeGrowthToken1 += feeGrowth;
+            } else {
+                self.protocolFeesToken2 += protocolFee - rebate;
+                self.feeGrowthToken2 += feeGrowth;
+            }
+            Ok(_amountIn - protocolFee)
+        }
+
+        // Returns the Token1 & Token2 LP fees earned by _provider, including those not yet settled
+        fn getAccruedFees(&self, _provider: AccountId) -> (Balance, Balance) {
+            let share = self.shares.get(_provider).unwrap_or(0);
+            let (paid1, paid2) = self.feeGrowthPaid.get(_provider).unwrap_or((0, 0));
+            let (accrued1, accrued2) = self.accruedFees.get(_provider).unwrap_or((0, 0));
+            // Fees earned are bounded by the reserves, so only the intermediate product can exceed u128
+            let earned = |feeGrowth: Balance, paid: Balance| {
+                super::mulDiv(share, feeGrowth - paid, super::FEE_GROWTH_PRECISION)
+                    .unwrap_or(Balance::MAX)
+            };
+            (
+                accrued1.saturating_add(earned(self.feeGrowthToken1, paid1)),
+                accrued2.saturating_add(earned(self.feeGrowthToken2, paid2)),
+            )
+        }
+
+        // Settles the LP fees earned by _provider so far, must be called before its shares change
+        fn settleFees(&mut self, _provider: AccountId) {
+            let accrued = self.getAccruedFees(_provider);
+            self.accruedFees.insert(_provider, &accrued);
+            self.feeGrowthPaid
+                .insert(_provider, &(self.feeGrowthToken1, self.feeGrowthToken2));
+        }
+
+        // Remembers _provider as having provided liquidity in the current block
+        fn recordActiveProvider(&mut self, _provider: AccountId) {
+            let block = self.env().block_number();
+            if self.activeProvidersBlock != block {
+                self.activeProvidersBlock = block;
+                self.activeProviders.clear();
+            }
+            if self.activeProviders.len() < super::MAX_ACTIVE_PROVIDERS
+                && !self.activeProviders.contains(&_provider)
+            {
+                self.activeProviders.push(_provider);
+            }
+        }
+
+        // Credits part of _protocolFee to the providers active in the current block, pro rata to their shares
+        // Returns the amount credited, which is always paid out of the protocol's cut
+        fn payActiveProviderRebate(&mut self, _protocolFee: Balance, _token1In: bool) -> Balance {
+            if self.activeProviderRebateBps == 0
+                || self.activeProvidersBlock != self.env().block_number()
+            {
+                return 0;
+            }
+            let rebate =
+                super::mulDiv(_protocolFee, self.activeProviderRebateBps, super::BPS).unwrap_or(0);
+            let providers = self.activeProviders.clone();
+            let activeShares: Balance = providers
+                .iter()
+                .map(|provider| self.shares.get(provider).unwrap_or(0))
+                .sum();
+            if rebate == 0 || activeShares == 0 {
+                return 0;
+            }
+
+            let mut paid = 0;
+            for provider in providers {
+                let amount =
+                    super::mulDiv(rebate, self.shares.get(provider).unwrap_or(0), activeShares)
+                        .unwrap_or(0);
+                // A rebate that cannot be delivered stays with the protocol
+                if self.pushToken(_token1In, provider, amount).is_ok() {
+                    paid += amount;
+                }
+            }
+            paid
+        }
+
+        // Moves _amount shares from _from to _to along with the LP fees they accrued
+        fn moveShares(
+            &mut self,
+            _from: AccountId,
+            _to: AccountId,
+            _amount: Balance,
+        ) -> Result<(), Error> {
+            let fromShares = self.shares.get(_from).unwrap_or(0);
+            match _amount {
+                0 => return Err(Error::ZeroAmount),
+                _ if _amount > fromShares => return Err(Error::InsufficientAmount),
+                _ => {}
+            }
+            if _from == _to {
+                return Ok(());
+            }
+            let remainingShares = fromShares - _amount;
+            if remainingShares != 0 && remainingShares < self.minRemainingShares {
+                return Err(Error::DustPositionNotAllowed);
+            }
+            let toShares = self.shares.get(_to).unwrap_or(0);
+            let isNewProvider = toShares == 0;
+            if isNewProvider
+                && remainingShares != 0
+                && self.maxProviders != 0
+                && self.providerCount >= self.maxProviders
+            {
+                return Err(Error::ProviderLimitReached);
+            }
+
+            self.settleFees(_from);
+            self.settleFees(_to);
+            let (fromAccrued1, fromAccrued2) = self.accruedFees.get(_from).unwrap_or((0, 0));
+            let (movedAccrued1, movedAccrued2) = (
+                fromAccrued1 * _amount / fromShares,
+                fromAccrued2 * _amount / fromShares,
+            );
+            let (toAccrued1, toAccrued2) = self.accruedFees.get(_to).unwrap_or((0, 0));
+            self.accruedFees.insert(
+                _from,
+                &(fromAccrued1 - movedAccrued1, fromAccrued2 - movedAccrued2),
+            );
+            self.accruedFees.insert(
+                _to,
+                &(toAccrued1 + movedAccrued1, toAccrued2 + movedAccrued2),
+            );
+            self.shares.insert(_from, &remainingShares);
+            self.shares.insert(_to, &(toShares + _amount));
+            if isNewProvider {
+                self.providerCount += 1;
+            }
+            if remainingShares == 0 {
+                self.providerCount -= 1;
+            }
+            self.recordProviderValue(_from);
+            self.recordProviderValue(_to);
+
+            self.env().emit_event(Transfer {
+                from: _from,
+                to: _to,
+                value: _amount,
+            });
+            Ok(())
+        }
+
+        // Appends the value of _provider's position in Token1 to its history if it opted in
+        fn recordProviderValue(&mut self, _provider: AccountId) {
+            let mut history = match self.valueHistory.get(_provider) {
+                Some(history) => history,
+                None => return,
+            };
+            let value = if self.totalShares == 0 || self.totalToken2 == 0 {
+                0
+            } else {
+                // Saturates rather than failing the provide or withdraw that records the value
+                let share = self.shares.get(_provider).unwrap_or(0);
+                let token1 = super::mulDiv(share, self.totalToken1, self.totalShares)
+                    .unwrap_or(Balance::MAX);
+                let token2 = super::mulDiv(share, self.totalToken2, self.totalShares)
+                    .unwrap_or(Balance::MAX);
+                let token2InToken1 = super::mulDiv(token2, self.totalToken1, self.totalToken2)
+                    .unwrap_or(Balance::MAX);
+                token1.saturating_add(token2InToken1)
+            };
+            if history.len() >= super::MAX_VALUE_HISTORY {
+                history.remove(0);
+            }
+            history.push((self.env().block_number(), value));
+            self.valueHistory.insert(_provider, &history);
+        }
+
+        // Returns CooldownActive if _recipient received swap output less than swapOutCooldownBlocks ago
+        fn swapOutCooldownCheck(&self, _recipient: AccountId) -> Result<(), Error> {
+            if self.swapOutCooldownBlocks == 0 {
+                return Ok(());
+            }
+            match self.lastSwapOutBlock.get(_recipient) {
+                Some(last) if self.env().block_number() < last + self.swapOutCooldownBlocks => {
+                    Err(Error::CooldownActive)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        // Used to restrict pool configuration to the owner
+        fn onlyOwner(&self) -> Result<(), Error> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner);
+            }
+            Ok(())
+        }
+
+        // Used to restrict sensitive configuration (fees, pauses) to the owner, at most once per adminActionCooldown
+        fn sensitiveAdminAction(&mut self) -> Result<(), Error> {
+            self.onlyOwner()?;
+            let now = self.env().block_timestamp();
+            if let Some(last) = self.lastAdminAction {
+                if now < last + self.adminActionCooldown {
+                    return Err(Error::AdminCooldownActive);
+                }
+            }
+            self.lastAdminAction = Some(now);
+            Ok(())
+        }
+
+        // Rejects provides whose implied price deviates from spot by more than maxProvideDeviationBps
+        fn providePriceCheck(
+            &self,
+            _amountToken1: Balance,
+            _amountToken2: Balance,
+        ) -> Result<(), Error> {
+            // Token2 matching _amountToken1 at spot, too large to match any _amountToken2 on overflow
+            let expected = super::mulDiv(self.totalToken2, _amountToken1, self.totalToken1)
+                .ok_or(Error::NonEquivalentValue)?;
+            let allowed = super::mulDiv(expected, self.maxProvideDeviationBps, super::BPS)
+                .ok_or(Error::NonEquivalentValue)?;
+
+            if _amountToken2.abs_diff(expected) > allowed {
+                return Err(Error::NonEquivalentValue);
+            }
+            Ok(())
+        }
+    }
+
+    impl Dex {
+        /// Constructs a new constant product AMM instance
+        /// @param _fees: valid interval -> [0,1000)
+        #[ink(constructor)]
+        pub fn new(_fees: Balance) -> Self {
+            Self::newWeighted(_fees, 50)
+        }
+
+        /// Constructs a new weighted AMM instance
+        /// @param _fees: valid interval -> [0,1000)
+        /// @param _weightToken1: valid interval -> [1,99], Token2 receives the remainder of 100
+        #[ink(constructor)]
+        pub fn newWeighted(_fees: Balance, _weightToken1: u8) -> Self {
+            // Sets weights to 50/50 if not in valid range
+            let weightToken1 = if _weightToken1 == 0 || _weightToken1 >= 100 {
+                50
+            } else {
+                _weightToken1
+            };
+
+            // Sets fees to zero if not in valid range
+            Self {
+                totalShares: 0,
+                totalToken1: 0,
+                totalToken2: 0,
+                shares: Mapping::default(),
+                token1Balance: Mapping::default(),
+                token2Balance: Mapping::default(),
+                fees: if _fees >= 1000 { 0 } else { _fees },
+                owner: Self::env().caller(),
+                // Generous by default so that normal provides are never rejected
+                maxProvideDeviationBps: 1_000,
+                weightToken1,
+                weightToken2: 100 - weightToken1,
+                minLiquidityForSwaps: 0,
+                minSwapToken1: 0,
+                minSwapToken2: 0,
+                providerCount: 0,
+                maxProviders: 0,
+                deployedAt: Self::env().block_number(),
+                tradeCount: 0,
+                checkpoints: Vec::new(),
+                dynamicFee: false,
+                minFeeBps: 0,
+                maxFeeBps: 0,
+                imbalanceFee: false,
+                faucetEnabled: false,
+                carbonToken: None,
+                protocolFeeBps: 0,
+                protocolFeesToken1: 0,
+                protocolFeesToken2: 0,
+                treasury: Self::env().caller(),
+                deniedRecipients: Mapping::default(),
+                defaultSlippageBps: 50,
+                adminActionCooldown: 0,
+                lastAdminAction: None,
+                targetRatio: super::PRECISION,
+                volume: Mapping::default(),
+                quoteIsToken2: true,
+                valueHistory: Mapping::default(),
+                swapOutCooldownBlocks: 0,
+                lastSwapOutBlock: Mapping::default(),
+                paused: false,
+                activeProviderRebateBps: 0,
+                activeProvidersBlock: 0,
+                activeProviders: Vec::new(),
+                emergencyTimelock: super::DEFAULT_EMERGENCY_TIMELOCK,
+                emergencyExecutableAt: None,
+                minRemainingShares: 0,
+                principalToken1: 0,
+                principalToken2: 0,
+                token1: None,
+                token2: None,
+                feeGrowthToken1: 0,
+                feeGrowthToken2: 0,
+                feeGrowthPaid: Mapping::default(),
+                accruedFees: Mapping::default(),
+                shareAllowances: Mapping::default(),
+                price1CumulativeLast: 0,
+                price2CumulativeLast: 0,
+                blockTimestampLast: Self::env().block_timestamp(),
+                locked: Default::default(),
+            }
+        }
+
+        /// Constructs a new constant product AMM instance whose swap fee can scale with each trade's price impact
+        /// @param _fees: valid interval -> [0,1000), charged when _imbalanceFee is false
+        /// @param _imbalanceFee: enables the price impact scaled fee, see getEffectiveFee
+        /// @param _minFeeBps, _maxFeeBps: fee bounds in basis points, valid interval -> _minFeeBps <= _maxFeeBps < BPS
+        #[ink(constructor)]
+        pub fn newWithImbalanceFee(
+            _fees: Balance,
+            _imbalanceFee: bool,
+            _minFeeBps: Balance,
+            _maxFeeBps: Balance,
+        ) -> Self {
+            let mut dex = Self::new(_fees);
+            // Keeps the static fee if the bounds are not in valid range
+            if _minFeeBps <= _maxFeeBps && _maxFeeBps < super::BPS {
+                dex.imbalanceFee = _imbalanceFee;
+                dex.minFeeBps = _minFeeBps;
+                dex.maxFeeBps = _maxFeeBps;
+            }
+            dex
+        }
+
+        /// Constructs a new AMM instance with a configured quote token
+        /// @param _fees: valid interval -> [0,1000)
+        /// @param _quoteIsToken2: Token2 is the quote token if true, Token1 otherwise
+        #[ink(constructor)]
+        pub fn newWithQuote(_fees: Balance, _quoteIsToken2: bool) -> Self {
+            let mut dex = Self::new(_fees);
+            dex.quoteIsToken2 = _quoteIsToken2;
+            dex
+        }
+
+        /// Constructs a new constant product AMM instance rejecting swaps selling less than a minimum amount
+        /// @param _fees: valid interval -> [0,1000)
+        /// @param _minSwapToken1: minimum amount of Token1 sold by a swap
+        /// @param _minSwapToken2: minimum amount of Token2 sold by a swap
+        #[ink(constructor)]
+        pub fn newWithMinSwap(
+            _fees: Balance,
+            _minSwapToken1: Balance,
+            _minSwapToken2: Balance,
+        ) -> Self {
+            let mut dex = Self::new(_fees);
+            dex.minSwapToken1 = _minSwapToken1;
+            dex.minSwapToken2 = _minSwapToken2;
+            dex
+        }
+
+        /// Constructs a new constant product AMM instance routing part of the swap fee to a treasury
+        /// @param _fees: valid interval -> [0,1000)
+        /// @param _treasury: account receiving the protocol fees on collectProtocolFees
+        /// @param _protocolFeeBps: share of the swap fee kept by the protocol, valid interval -> [0,10000]
+        #[ink(constructor)]
+        pub fn newWithTreasury(
+            _fees: Balance,
+            _treasury: AccountId,
+            _protocolFeeBps: Balance,
+        ) -> Self {
+            let mut dex = Self::new(_fees);
+            dex.treasury = _treasury;
+            // Sets the protocol fee to zero if not in valid range
+            dex.protocolFeeBps = if _protocolFeeBps > super::BPS {
+                0
+            } else {
+                _protocolFeeBps
+            };
+            dex
+        }
+
+        /// Constructs a new constant product AMM instance trading the tokens of two CarbonToken contracts
+        /// Providers & traders must approve the pool on the token contracts before providing or swapping
+        /// @param _fees: valid interval -> [0,1000)
+        #[ink(constructor)]
+        pub fn newWithTokens(_fees: Balance, _token1: AccountId, _token2: AccountId) -> Self {
+            let mut dex = Self::new(_fees);
+            dex.token1 = Some(_token1);
+            dex.token2 = Some(_token2);
+            dex
+        }
+
+        /// Returns true if Token2 is the quote token, false if Token1 is
+        #[ink(message)]
+        pub fn getQuoteToken(&self) -> bool {
+            self.quoteIsToken2
+        }
+
+        /// Returns the spot price scaled by PRECISION, of Token1 in Token2 if _token1InToken2 is true
+        /// & of Token2 in Token1 otherwise. Defaults to the base token priced in the quote token
+        #[ink(message)]
+        pub fn getPrice(&self, _token1InToken2: Option<bool>) -> Result<Balance, Error> {
+            self.activePool()?;
+            self.getSpotPrice(_token1InToken2.unwrap_or(self.quoteIsToken2))
+                .ok_or(Error::InsufficientLiquidity)
+        }
+
+        /// Returns the marginal price of Token1 in Token2, scaled by PRECISION
+        /// The price is before fees & slippage, so actual swaps of any size receive less
+        #[ink(message)]
+        pub fn getSpotPriceToken1(&self) -> Result<Balance, Error> {
+            self.getPrice(Some(true))
+        }
+
+        /// Returns the marginal price of Token2 in Token1, scaled by PRECISION
+        /// The price is before fees & slippage, so actual swaps of any size receive less
+        #[ink(message)]
+        pub fn getSpotPriceToken2(&self) -> Result<Balance, Error> {
+            self.getPrice(Some(false))
+        }
+
+        /// Returns the cumulative Token1 in Token2 & Token2 in Token1 prices, scaled by PRECISION & summed
+        /// per millisecond held, along with the time of their last update. Sampling twice & dividing the
+        /// wrapping difference of the accumulators by the elapsed time gives the time-weighted average price
+        #[ink(message)]
+        pub fn getCumulativePrices(&self) -> (Balance, Balance, Timestamp) {
+            (
+                self.price1CumulativeLast,
+                self.price2CumulativeLast,
+                self.blockTimestampLast,
+            )
+        }
+
+        /// Returns the number of accounts holding shares & the maximum allowed (0 for unlimited)
+        #[ink(message)]
+        pub fn getProviderLimit(&self) -> (u32, u32) {
+            (self.providerCount, self.maxProviders)
+        }
+
+        /// Sets the maximum number of accounts holding shares, 0 for unlimited
+        /// Existing providers can always add more liquidity. Only callable by the owner
+        #[ink(message)]
+        pub fn setMaxProviders(&mut self, _maxProviders: u32) -> Result<(), Error> {
+            self.onlyOwner()?;
+            self.maxProviders = _maxProviders;
+            Ok(())
+        }
+
+        /// Returns the minimum amounts of Token1 & Token2 sold by a swap
+        #[ink(message)]
+        pub fn getMinSwapAmounts(&self) -> (Balance, Balance) {
+            (self.minSwapToken1, self.minSwapToken2)
+        }
+
+        /// Returns the liquidity constant (K) the pool must exceed before swaps are enabled
+        #[ink(message)]
+        pub fn getMinLiquidityForSwaps(&self) -> Balance {
+            self.minLiquidityForSwaps
+        }
+
+        /// Sets the liquidity constant (K) the pool must exceed before swaps are enabled
+        /// Providing liquidity is always allowed so the pool can bootstrap. Only callable by the owner
+        #[ink(message)]
+        pub fn setMinLiquidityForSwaps(&mut self, _minK: Balance) -> Result<(), Error> {
+            self.onlyOwner()?;
+            self.minLiquidityForSwaps = _minK;
+            Ok(())
+        }
+
+        /// Returns the number of blocks since the pool was deployed
+        #[ink(message)]
+        pub fn getPoolAge(&self) -> BlockNumber {
+            self.env().block_number() - self.deployedAt
+        }
+
+        /// Returns the approximate number of swaps per block since deployment, scaled by PRECISION
+        #[ink(message)]
+        pub fn getSwapsPerBlock(&self) -> Balance {
+            let poolAge = self.getPoolAge().max(1);
+            self.tradeCount * super::PRECISION / poolAge as Balance
+        }
+
+        /// Returns the recent (block, totalToken1, totalToken2) checkpoints, oldest first
+        #[ink(message)]
+        pub fn getCheckpoints(&self) -> Vec<(BlockNumber, Balance, Balance)> {
+            self.checkpoints.clone()
+        }
+
+        /// Returns the change in reserve imbalance between the two most recent checkpoints
+        /// Imbalance is (totalToken1 - totalToken2) / (totalToken1 + totalToken2) in basis points, so a positive
+        /// trend means Token1 is growing relative to Token2
+        #[ink(message)]
+        pub fn getImbalanceTrend(&self) -> Result<i128, Error> {
+            let count = self.checkpoints.len();
+            if count < 2 {
+                return Err(Error::InsufficientHistory);
+            }
+
+            let imbalance = |(_, token1, token2): (BlockNumber, Balance, Balance)| -> i128 {
+                if token1 + token2 == 0 {
+                    return 0;
+                }
+                (token1 as i128 - token2 as i128) * super::BPS as i128 / (token1 + token2) as i128
+            };
+            Ok(imbalance(self.checkpoints[count - 1]) - imbalance(self.checkpoints[count - 2]))
+        }
+
+        /// Returns the fee tier of _user and the swap fee it is currently charged, in basis points
+        /// Tiers are reached by swap volume, see FEE_TIERS
+        #[ink(message)]
+        pub fn getUserFeeTier(&self, _user: AccountId) -> (u8, Balance) {
+            self.getFeeTier(_user, 0)
+        }
+
+        /// Returns the swap fee currently charged to the caller, in basis points
+        #[ink(message)]
+        pub fn getSwapFeeBps(&self) -> Balance {
+            self.getFeeBps(0)
+        }
+
+        /// Returns the swap fee charged to the caller for swapping _amountToken1 of Token1, in basis points
+        /// With the imbalance fee enabled it scales from minFeeBps to maxFeeBps with the trade's price impact,
+        /// BPS - BPS * (totalToken1 / (totalToken1 + _amountToken1))^2, reaching maxFeeBps at VOLATILITY_CAP_BPS
+        #[ink(message)]
+        pub fn getEffectiveFee(&self, _amountToken1: Balance) -> Balance {
+            self.getFeeBpsForAmountIn(self.totalToken1, _amountToken1)
+        }
+
+        /// Enables or disables the volatility scaled swap fee and sets its bounds in basis points
+        /// When disabled the static fees parameter applies. Only callable by the owner
+        #[ink(message)]
+        pub fn setDynamicFee(
+            &mut self,
+            _enabled: bool,
+            _minFeeBps: Balance,
+            _maxFeeBps: Balance,
+        ) -> Result<(), Error> {
+            self.onlyOwner()?;
+            if _minFeeBps > _maxFeeBps || _maxFeeBps >= super::BPS {
+                return Err(Error::InvalidFeeBounds);
+            }
+            self.sensitiveAdminAction()?;
+
+            self.dynamicFee = _enabled;
+            self.minFeeBps = _minFeeBps;
+            self.maxFeeBps = _maxFeeBps;
+            Ok(())
+        }
+
+        /// Returns the share of the swap fee kept by the protocol, in basis points of the fee
+        #[ink(message)]
+        pub fn getProtocolFeeBps(&self) -> Balance {
+            self.protocolFeeBps
+        }
+
+        /// Sets the share of the swap fee kept by the protocol, in basis points of the fee
+        /// The remainder of the fee stays in the reserves for the LPs. Only callable by the owner
+        #[ink(message)]
+        pub fn setProtocolFeeBps(&mut self, _protocolFeeBps: Balance) -> Result<(), Error> {
+            self.onlyOwner()?;
+            if _protocolFeeBps > super::BPS {
+                return Err(Error::InvalidFeeBounds);
+            }
+            self.sensitiveAdminAction()?;
+            self.protocolFeeBps = _protocolFeeBps;
+            Ok(())
+        }
+
+        /// Returns the accumulated Token1 & Token2 protocol fees
+        #[ink(message)]
+        pub fn getProtocolFees(&self) -> (Balance, Balance) {
+            (self.protocolFeesToken1, self.protocolFeesToken2)
+        }
+
+        /// Returns the account receiving the collected protocol fees
+        #[ink(message)]
+        pub fn getTreasury(&self) -> AccountId {
+            self.treasury
+        }
+
+        /// Sets the account receiving the collected protocol fees. Only callable by the owner
+        #[ink(message)]
+        pub fn setTreasury(&mut self, _treasury: AccountId) -> Result<(), Error> {
+            self.sensitiveAdminAction()?;
+            self.treasury = _treasury;
+            Ok(())
+        }
+
+        /// Sends the accumulated Token1 & Token2 protocol fees to the treasury. Only callable by the owner
+        /// Returns the amount of Token1 & Token2 collected
+        #[ink(message)]
+        pub fn collectProtocolFees(&mut self) -> Result<(Balance, Balance), Error> {
+            self.onlyOwner()?;
+            let (amountToken1, amountToken2) = (self.protocolFeesToken1, self.protocolFeesToken2);
+            self.protocolFeesToken1 = 0;
+            self.protocolFeesToken2 = 0;
+            self.pushToken(true, self.treasury, amountToken1)?;
+            self.pushToken(false, self.treasury, amountToken2)?;
+
+            self.env().emit_event(ProtocolFeesCollected {
+                treasury: self.treasury,
+                amountToken1,
+                amountToken2,
+            });
+            Ok((amountToken1, amountToken2))
+        }
+
+        /// Returns the accumulated protocol fees valued in Token1, converting Token2 fees at spot
+        #[ink(message)]
+        pub fn getProtocolRevenueInToken1(&self) -> Result<Balance, Error> {
+            self.activePool()?;
+            let token2InToken1 =
+                super::mulDiv(self.protocolFeesToken2, self.totalToken1, self.totalToken2)
+                    .ok_or(Error::InsufficientLiquidity)?;
+            self.protocolFeesToken1
+                .checked_add(token2InToken1)
+                .ok_or(Error::InsufficientLiquidity)
+        }
+
+        /// Returns true if _account may not receive swap output sent by another account
+        #[ink(message)]
+        pub fn isRecipientDenied(&self, _account: AccountId) -> bool {
+            self.deniedRecipients.get(_account).unwrap_or(false)
+        }
+
+        /// Adds _account to or removes it from the swap recipient deny list. Only callable by the owner
+        #[ink(message)]
+        pub fn setRecipientDenied(
+            &mut self,
+            _account: AccountId,
+            _denied: bool,
+        ) -> Result<(), Error> {
+            self.onlyOwner()?;
+            if _denied {
+                self.deniedRecipients.insert(_account, &true);
+            } else {
+                self.deniedRecipients.remove(_account);
+            }
+            Ok(())
+        }
+
+        /// Returns the recommended slippage tolerance of the pool, in basis points
+        #[ink(message)]
+        pub fn getDefaultSlippage(&self) -> Balance {
+            self.defaultSlippageBps
+        }
+
+        /// Sets the recommended slippage tolerance of the pool, in basis points. Only callable by the owner
+        #[ink(message)]
+        pub fn setDefaultSlippage(&mut self, _slippageBps: Balance) -> Result<(), Error> {
+            self.onlyOwner()?;
+            if _slippageBps > super::BPS {
+                return Err(Error::SlippageExceeded);
+            }
+            self.defaultSlippageBps = _slippageBps;
+            Ok(())
+        }
+
+        /// Returns the minimum time between sensitive admin actions & the time of the last one
+        #[ink(message)]
+        pub fn getAdminActionCooldown(&self) -> (Timestamp, Option<Timestamp>) {
+            (self.adminActionCooldown, self.lastAdminAction)
+        }
+
+        /// Sets the minimum time between sensitive admin actions (fee changes, pauses)
+        /// Changing the cooldown is itself a sensitive action. Only callable by the owner
+        #[ink(message)]
+        pub fn setAdminActionCooldown(&mut self, _cooldown: Timestamp) -> Result<(), Error> {
+            self.sensitiveAdminAction()?;
+            self.adminActionCooldown = _cooldown;
+            Ok(())
+        }
+
+        /// Returns the target totalToken2 / totalToken1 reserve ratio, scaled by PRECISION
+        #[ink(message)]
+        pub fn getTargetRatio(&self) -> Balance {
+            self.targetRatio
+        }
+
+        /// Sets the target totalToken2 / totalToken1 reserve ratio, scaled by PRECISION. Only callable by the owner
+        #[ink(message)]
+        pub fn setTargetRatio(&mut self, _targetRatio: Balance) -> Result<(), Error> {
+            self.onlyOwner()?;
+            if _targetRatio == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            self.targetRatio = _targetRatio;
+            Ok(())
+        }
+
+        /// Returns which token to sell (true: Token1, false: Token2) and roughly how much of it to move the
+        /// reserve ratio back to targetRatio, ignoring fees. Only a hint for keepers, nothing is executed
+        /// Not supported by weighted pools
+        #[ink(message)]
+        pub fn getRebalanceHint(&self) -> Result<(bool, Balance), Error> {
+            self.activePool()?;
+            if self.isWeighted() {
+                return Err(Error::UnsupportedPool);
+            }
+
+            // Reserves on the constant product curve at the target ratio
+            // targetToken1 = sqrt(K / targetRatio) & targetToken2 = sqrt(K * targetRatio)
+            let scaledToken2 = super::mulDiv(self.totalToken2, super::PRECISION, self.targetRatio)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let scaledToken1 = super::mulDiv(self.totalToken1, self.targetRatio, super::PRECISION)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let targetToken1 = super::sqrtProduct(self.totalToken1, scaledToken2);
+            let targetToken2 = super::sqrtProduct(self.totalToken2, scaledToken1);
+
+            if targetToken1 > self.totalToken1 {
+                Ok((true, targetToken1 - self.totalToken1))
+            } else {
+                Ok((false, targetToken2.saturating_sub(self.totalToken2)))
+            }
+        }
+
+        /// Returns the Token1 & Token2 weights of the pool, summing to 100
+        #[ink(message)]
+        pub fn getWeights(&self) -> (u8, u8) {
+            (self.weightToken1, self.weightToken2)
+        }
+
+        /// Returns the owner of the pool
+        #[ink(message)]
+        pub fn getOwner(&self) -> AccountId {
+            self.owner
+        }
+
+        /// Returns the maximum deviation (in basis points) allowed between a provide's implied price and spot
+        #[ink(message)]
+        pub fn getMaxProvideDeviation(&self) -> Balance {
+            self.maxProvideDeviationBps
+        }
+
+        /// Sets the maximum deviation (in basis points) allowed between a provide's implied price and spot
+        /// Only callable by the owner
+        #[ink(message)]
+        pub fn setMaxProvideDeviation(&mut self, _bps: Balance) -> Result<(), Error> {
+            self.onlyOwner()?;
+            self.maxProvideDeviationBps = _bps;
+            Ok(())
+        }
+
+        /// Sends free token(s) to the invoker
+        /// The faucet is disabled on deployment & must stay disabled on mainnet, where it would let anyone mint
+        /// the internal ledger tokens. Testnet deployments enable it through setFaucetEnabled. Pools backed by
+        /// token contracts never credit free tokens
+        #[ink(message)]
+        pub fn faucet(
+            &mut self,
+            _amountToken1: Balance,
+            _amountToken2: Balance,
+        ) -> Result<(), Error> {
+            if !self.faucetEnabled || self.token1.is_some() || self.token2.is_some() {
+                return Err(Error::FaucetDisabled);
+            }
+            let caller = self.env().caller();
+            let token1 = self.token1Balance.get(caller).unwrap_or(0);
+            let token2 = self.token2Balance.get(caller).unwrap_or(0);
+
+            self.token1Balance.insert(caller, &(token1 + _amountToken1));
+            self.token2Balance.insert(caller, &(token2 + _amountToken2));
+            Ok(())
+        }
+
+        /// Returns true if the faucet credits free tokens
+        #[ink(message)]
+        pub fn getFaucetEnabled(&self) -> bool {
+            self.faucetEnabled
+        }
+
+        /// Enables or disables the faucet, only for testnet deployments. Only callable by the owner
+        #[ink(message)]
+        pub fn setFaucetEnabled(&mut self, _enabled: bool) -> Result<(), Error> {
+            self.onlyOwner()?;
+            self.faucetEnabled = _enabled;
+            Ok(())
+        }
+
+        /// Returns the balance of the user
+        #[ink(message)]
+        pub fn getMyHoldings(&self) -> (Balance, Balance, Balance) {
+            let caller = self.env().caller();
+            let token1 = self.tokenBalanceOf(true, caller).unwrap_or(0);
+            let token2 = self.tokenBalanceOf(false, caller).unwrap_or(0);
+            let myShares = self.shares.get(caller).unwrap_or(0);
+            (token1, token2, myShares)
+        }
+
+        /// Returns the CarbonToken contract address used for portfolio queries
+        #[ink(message)]
+        pub fn getCarbonToken(&self) -> Option<AccountId> {
+            self.carbonToken
+        }
+
+        /// Sets the CarbonToken contract address used for portfolio queries. Only callable by the owner
+        #[ink(message)]
+        pub fn setCarbonToken(&mut self, _token: AccountId) -> Result<(), Error> {
+            self.onlyOwner()?;
+            self.carbonToken = Some(_token);
+            Ok(())
+        }
+
+        /// Returns the token contracts backing Token1 & Token2, None for a token kept in the internal ledger
+        #[ink(message)]
+        pub fn getTokens(&self) -> (Option<AccountId>, Option<AccountId>) {
+            (self.token1, self.token2)
+        }
+
+        /// Returns the CarbonToken balance of _user along with its Token1, Token2 & share holdings in the pool
+        #[ink(message)]
+        pub fn getCombinedPosition(
+            &self,
+            _user: AccountId,
+        ) -> Result<(Balance, Balance, Balance, Balance), Error> {
+            let carbonBalance = self.getCarbonTokenBalance(_user)?;
+            let token1 = self.tokenBalanceOf(true, _user)?;
+            let token2 = self.tokenBalanceOf(false, _user)?;
+            let userShares = self.shares.get(_user).unwrap_or(0);
+            Ok((carbonBalance, token1, token2, userShares))
+        }
+
+        /// Returns the largest share of totalShares held by a single account of _providers in basis points
+        #[ink(message)]
+        pub fn getConcentration(&self, _providers: Vec<AccountId>) -> u16 {
+            if self.totalShares == 0 {
+                return 0;
+            }
+            let largest = _providers
+                .iter()
+                .map(|provider| self.shares.get(provider).unwrap_or(0))
+                .max()
+                .unwrap_or(0);
+            (largest * super::BPS / self.totalShares) as u16
+        }
+
+        /// Opts the caller in or out of recording its position value in Token1 on each provide & withdraw
+        #[ink(message)]
+        pub fn setValueHistoryOptIn(&mut self, _enabled: bool) {
+            let caller = self.env().caller();
+            if !_enabled {
+                self.valueHistory.remove(caller);
+            } else if !self.valueHistory.contains(caller) {
+                self.valueHistory
+                    .insert(caller, &Vec::<(BlockNumber, Balance)>::new());
+            }
+        }
+
+        /// Returns the recorded (block, position value in Token1) history of _provider, oldest first
+        #[ink(message)]
+        pub fn getProviderValueHistory(&self, _provider: AccountId) -> ValueHistory {
+            self.valueHistory.get(_provider).unwrap_or_default()
+        }
+
+        /// Returns the number of blocks between two swap outputs to the same recipient, 0 if disabled
+        #[ink(message)]
+        pub fn getSwapOutCooldown(&self) -> BlockNumber {
+            self.swapOutCooldownBlocks
+        }
+
+        /// Sets the number of blocks between two swap outputs to the same recipient, 0 to disable
+        #[ink(message)]
+        pub fn setSwapOutCooldown(&mut self, _blocks: BlockNumber) -> Result<(), Error> {
+            self.onlyOwner()?;
+            self.swapOutCooldownBlocks = _blocks;
+            Ok(())
+        }
+
+        /// Returns the reserves, shares, trade count & status of the pool in a single call
+        #[ink(message)]
+        pub fn stats(&self) -> PoolStats {
+            PoolStats {
+                totalToken1: self.totalToken1,
+                totalToken2: self.totalToken2,
+                totalShares: self.totalShares,
+                tradeCount: self.tradeCount,
+                isActive: self.activePool().is_ok(),
+                paused: self.paused,
+            }
+        }
+
+        /// Returns the balances, shares, pool ownership & withdrawable amounts of _account in a single call
+        #[ink(message)]
+        pub fn getPosition(&self, _account: AccountId) -> Position {
+            let shares = self.shares.get(_account).unwrap_or(0);
+            let ownershipBps = (shares * super::BPS)
+                .checked_div(self.totalShares)
+                .unwrap_or(0);
+            let (withdrawableToken1, withdrawableToken2) =
+                self.getWithdrawEstimate(shares).unwrap_or((0, 0));
+            Position {
+                token1: self.tokenBalanceOf(true, _account).unwrap_or(0),
+                token2: self.tokenBalanceOf(false, _account).unwrap_or(0),
+                shares,
+                ownershipBps,
+                withdrawableToken1,
+                withdrawableToken2,
+            }
+        }
+
+        /// Returns the share of the protocol fee rebated to providers active in the swap's block, in basis points
+        #[ink(message)]
+        pub fn getActiveProviderRebate(&self) -> Balance {
+            self.activeProviderRebateBps
+        }
+
+        /// Sets the share of the protocol fee rebated to providers active in the swap's block, 0 to disable
+        /// The rebate is split pro rata to shares among accounts that provided liquidity in the same block
+        /// as the swap, rewarding depth added when it is needed. It is paid out of the protocol's cut only,
+        /// so traders pay the same fee & passive providers earn the same as without it. A trader providing
+        /// in its own block recovers at most this share of the protocol fee, never of the LP fee
+        #[ink(message)]
+        pub fn setActiveProviderRebate(&mut self, _rebateBps: Balance) -> Result<(), Error> {
+            self.onlyOwner()?;
+            if _rebateBps > super::BPS {
+                return Err(Error::InvalidFeeBounds);
+            }
+            self.sensitiveAdminAction()?;
+            self.activeProviderRebateBps = _rebateBps;
+            Ok(())
+        }
+
+        /// Returns the emergency withdrawal timelock & the execution time of the pending request, if any
+        #[ink(message)]
+        pub fn getEmergencyTimelock(&self) -> (Timestamp, Option<Timestamp>) {
+            (self.emergencyTimelock, self.emergencyExecutableAt)
+        }
+
+        /// Sets the delay between an emergency request & its execution, not allowed while a request is pending
+        #[ink(message)]
+        pub fn setEmergencyTimelock(&mut self, _timelock: Timestamp) -> Result<(), Error> {
+            self.onlyOwner()?;
+            if self.emergencyExecutableAt.is_some() {
+                return Err(Error::TimelockActive);
+            }
+            self.sensitiveAdminAction()?;
+            self.emergencyTimelock = _timelock;
+            Ok(())
+        }
+
+        /// Starts the timelock of an emergency withdrawal of the reserves, giving providers time to exit
+        #[ink(message)]
+        pub fn requestEmergency(&mut self) -> Result<Timestamp, Error> {
+            self.onlyOwner()?;
+            let executableAt = self.env().block_timestamp() + self.emergencyTimelock;
+            self.emergencyExecutableAt = Some(executableAt);
+            self.env().emit_event(EmergencyRequested {
+                owner: self.owner,
+                executableAt,
+            });
+            Ok(executableAt)
+        }
+
+        /// Moves the remaining reserves to the owner once the timelock elapsed & pauses the pool
+        /// Returns the amount of Token1 & Token2 withdrawn
+        #[ink(message)]
+        pub fn executeEmergency(&mut self) -> Result<(Balance, Balance), Error> {
+            self.onlyOwner()?;
+            let executableAt = self
+                .emergencyExecutableAt
+                .ok_or(Error::EmergencyNotRequested)?;
+            if self.env().block_timestamp() < executableAt {
+                return Err(Error::TimelockActive);
+            }
+
+            let (amountToken1, amountToken2) = (self.totalToken1, self.totalToken2);
+            self.pushToken(true, self.owner, amountToken1)?;
+            self.pushToken(false, self.owner, amountToken2)?;
+            self.totalToken1 = 0;
+            self.totalToken2 = 0;
+            self.principalToken1 = 0;
+            self.principalToken2 = 0;
+            self.emergencyExecutableAt = None;
+            // Outstanding shares no longer have reserves backing them
+            self.paused = true;
+
+            self.env().emit_event(EmergencyExecuted {
+                owner: self.owner,
+                amountToken1,
+                amountToken2,
+            });
+            Ok((amountToken1, amountToken2))
+        }
+
+        /// Returns true if provides, withdraws & swaps are halted
+        #[ink(message)]
+        pub fn isPaused(&self) -> bool {
+            self.paused
+        }
+
+        /// Halts provides, withdraws & swaps until unpaused. Only callable by the owner
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<(), Error> {
+            self.sensitiveAdminAction()?;
+            self.paused = true;
+            self.env().emit_event(Paused { owner: self.owner });
+            Ok(())
+        }
+
+        /// Resumes provides, withdraws & swaps. Only callable by the owner
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<(), Error> {
+            self.sensitiveAdminAction()?;
+            self.paused = false;
+            self.env().emit_event(Unpaused { owner: self.owner });
+            Ok(())
+        }
+
+        /// Returns the minimum non-zero amount of shares a provider must keep after a partial withdraw
+        #[ink(message)]
+        pub fn getMinRemainingShares(&self) -> Balance {
+            self.minRemainingShares
+        }
+
+        /// Sets the minimum non-zero amount of shares a provider must keep after a partial withdraw
+        #[ink(message)]
+        pub fn setMinRemainingShares(&mut self, _minShares: Balance) -> Result<(), Error> {
+            self.onlyOwner()?;
+            self.minRemainingShares = _minShares;
+            Ok(())
+        }
+
+        /// Returns the principal Token1 & Token2 deposited by providers, followed by the Token1 & Token2
+        /// accrued on top of it through swaps. Swaps also shift reserves between the two tokens,
+        /// so the accrued part of a token is zero while its reserve is below its principal
+        #[ink(message)]
+        pub fn getTVLBreakdown(&self) -> (Balance, Balance, Balance, Balance) {
+            (
+                self.principalToken1,
+                self.principalToken2,
+                self.totalToken1.saturating_sub(self.principalToken1),
+                self.totalToken2.saturating_sub(self.principalToken2),
+            )
+        }
+
+        /// Swaps the accumulated Token2 protocol fees for Token1 through the pool & retires the acquired
+        /// Token1, which must be backed by the CarbonToken contract. Restricted to the treasury, which is
+        /// the recipient of the protocol fees. Returns the amount of Token1 retired
+        #[ink(message)]
+        pub fn buybackAndRetire(&mut self, _minTokensRetired: Balance) -> Result<Balance, Error> {
+            if self.env().caller() != self.treasury {
+                return Err(Error::NotTreasury);
+            }
+            self.notPausedCheck()?;
+            self.activePool()?;
+            let amountIn = self.protocolFeesToken2;
+            if amountIn == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            // Only CarbonTokens held as the Token1 reserve may be retired
+            if self.carbonToken.is_none() {
+                return Err(Error::TokenNotSet);
+            }
+            if self.token1 != self.carbonToken {
+                return Err(Error::UnsupportedPool);
+            }
+
+            let amountOut =
+                self.getAmountOutForReserves(amountIn, self.totalToken2, self.totalToken1, false)?;
+            if amountOut < _minTokensRetired {
+                return Err(Error::SlippageExceeded);
+            }
+            self.protocolFeesToken2 = 0;
+            self.updateCumulativePrices();
+            self.totalToken2 += amountIn;
+            self.totalToken1 -= amountOut;
+            self.tradeCount += 1;
+            self.recordCheckpoint();
+
+            self.retireCarbonTokens(amountOut)?;
+            Ok(amountOut)
+        }
+
+        /// Returns the amount of tokens locked in the pool,total shares issued & trading fee param
+        #[ink(message)]
+        pub fn getPoolDetails(&self) -> (Balance, Balance, Balance, Balance) {
+            (
+                self.totalToken1,
+                self.totalToken2,
+                self.totalShares,
+                self.fees,
+            )
+        }
+
+        /// Returns the fee inclusive prices of Token1 in Token2, scaled by PRECISION
+        /// bid: Token2 received per Token1 sold, ask: Token2 paid per Token1 bought (both before slippage)
+        #[ink(message)]
+        pub fn getBidAsk(&self) -> Result<(Balance, Balance), Error> {
+            self.activePool()?;
+            let mid = self
+                .getSpotPrice(true)
+                .ok_or(Error::InsufficientLiquidity)?;
+
+            let feeBps = self.getFeeBps(0);
+            let bid = super::mulDiv(mid, super::BPS - feeBps, super::BPS)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let ask = super::mulDiv(mid, super::BPS, super::BPS - feeBps)
+                .ok_or(Error::InsufficientLiquidity)?;
+            Ok((bid, ask))
+        }
+
+        /// Returns amount of Token1 required when providing liquidity with _amountToken2 quantity of Token2
+        #[ink(message)]
+        pub fn getEquivalentToken1Estimate(
+            &self,
+            _amountToken2: Balance,
+        ) -> Result<Balance, Error> {
+            self.activePool()?;
+            Ok(self.totalToken1 * _amountToken2 / self.totalToken2)
+        }
+
+        /// Returns amount of Token2 required when providing liquidity with _amountToken1 quantity of Token1
+        #[ink(message)]
+        pub fn getEquivalentToken2Estimate(
+            &self,
+            _amountToken1: Balance,
+        ) -> Result<Balance, Error> {
+            self.activePool()?;
+            Ok(self.totalToken2 * _amountToken1 / self.totalToken1)
+        }
+
+        /// Returns amount of the opposite token required when providing liquidity with _amount of
+        /// Token1 if _tokenIsToken1 is true, of Token2 otherwise
+        #[ink(message)]
+        pub fn getMatchingAmount(
+            &self,
+            _tokenIsToken1: bool,
+            _amount: Balance,
+        ) -> Result<Balance, Error> {
+            if _tokenIsToken1 {
+                self.getEquivalentToken2Estimate(_amount)
+            } else {
+                self.getEquivalentToken1Estimate(_amount)
+            }
+        }
+
+        /// Returns the minimum amount of share that `provide` would issue for _amountToken1 & _amountToken2 at current reserves
+        /// Callers can pass the result as the share floor of a slippage-tolerant provide
+        #[ink(message)]
+        pub fn getMinSharesForAmounts(
+            &self,
+            _amountToken1: Balance,
+            _amountToken2: Balance,
+        ) -> Result<Balance, Error> {
+            if self.totalShares == 0 {
+                // Genesis liquidity is issued 100 Shares, minus the locked MINIMUM_LIQUIDITY
+                return Ok(100 * super::PRECISION - super::MINIMUM_LIQUIDITY);
+            }
+
+            let share1 = self.totalShares * _amountToken1 / self.totalToken1;
+            let share2 = self.totalShares * _amountToken2 / self.totalToken2;
+            Ok(share1.min(share2))
+        }
+
+        /// Validates a swap path of directions (true: Token1 -> Token2, false: Token2 -> Token1)
+        /// The path may not exceed MAX_PATH_HOPS: within a single pool each further hop sells back the
+        /// token received from the previous one, a round trip that only pays fees
+        #[ink(message)]
+        pub fn validatePath(&self, _path: Vec<bool>) -> Result<(), Error> {
+            if _path.is_empty() || _path.len() > super::MAX_PATH_HOPS {
+                return Err(Error::InvalidPath);
+            }
+            Ok(())
+        }
+
+        /// Returns the amounts obtained at each hop of _path when swapping _amountIn, starting with _amountIn
+        /// Reserves are updated between hops as the swaps would update them
+        #[ink(message)]
+        pub fn getAmountsOut(
+            &self,
+            _amountIn: Balance,
+            _path: Vec<bool>,
+        ) -> Result<Vec<Balance>, Error> {
+            self.activePool()?;
+            self.validatePath(_path.clone())?;
+
+            let mut reserve1 = self.totalToken1;
+            let mut reserve2 = self.totalToken2;
+            let mut amounts = Vec::new();
+            amounts.push(_amountIn);
+
+            let mut amountIn = _amountIn;
+            for token1In in _path {
+                let amountOut = if token1In {
+                    let amountOut =
+                        self.getAmountOutForReserves(amountIn, reserve1, reserve2, true)?;
+                    reserve1 += amountIn;
+                    reserve2 -= amountOut;
+                    amountOut
+                } else {
+                    let amountOut =
+                        self.getAmountOutForReserves(amountIn, reserve2, reserve1, false)?;
+                    reserve2 += amountIn;
+                    reserve1 -= amountOut;
+                    amountOut
+                };
+                amounts.push(amountOut);
+                amountIn = amountOut;
+            }
+            Ok(amounts)
+        }
+
+        /// Returns the output of swapping _amountIn against arbitrary reserves at the fee the pool would charge,
+        /// using the Uniswap V2 router formula. Not supported by weighted pools
+        #[ink(message)]
+        pub fn getAmountOut(
+            &self,
+            _amountIn: Balance,
+            _reserveIn: Balance,
+            _reserveOut: Balance,
+        ) -> Result<Balance, Error> {
+            if self.isWeighted() {
+                return Err(Error::UnsupportedPool);
+            }
+            if _amountIn == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            if _reserveIn == 0 || _reserveOut == 0 {
+                return Err(Error::ZeroLiquidity);
+            }
+            let feeBps = self.getFeeBpsForAmountIn(_reserveIn, _amountIn);
+            // amountIn * (BPS - fee) * reserveOut / (reserveIn * BPS + amountIn * (BPS - fee))
+            let amountInWithFee = _amountIn
+                .checked_mul(super::BPS - feeBps)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let denominator = _reserveIn
+                .checked_mul(super::BPS)
+                .and_then(|reserve| reserve.checked_add(amountInWithFee))
+                .ok_or(Error::InsufficientLiquidity)?;
+            super::mulDiv(amountInWithFee, _reserveOut, denominator)
+                .ok_or(Error::InsufficientLiquidity)
+        }
+
+        /// Returns the input required to obtain _amountOut from arbitrary reserves at the fee the pool would charge,
+        /// using the Uniswap V2 router formula. Fails if _amountOut is not below _reserveOut. Not supported by
+        /// weighted pools
+        #[ink(message)]
+        pub fn getAmountIn(
+            &self,
+            _amountOut: Balance,
+            _reserveIn: Balance,
+            _reserveOut: Balance,
+        ) -> Result<Balance, Error> {
+            if self.isWeighted() {
+                return Err(Error::UnsupportedPool);
+            }
+            if _amountOut == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            if _reserveIn == 0 || _reserveOut == 0 {
+                return Err(Error::ZeroLiquidity);
+            }
+            if _amountOut >= _reserveOut {
+                return Err(Error::InsufficientLiquidity);
+            }
+            let feeBps = self.getFeeBpsForAmountOut(_reserveOut, _amountOut);
+            // reserveIn * amountOut * BPS / ((reserveOut - amountOut) * (BPS - fee)) + 1
+            let numerator = _amountOut
+                .checked_mul(super::BPS)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let denominator = (_reserveOut - _amountOut)
+                .checked_mul(super::BPS - feeBps)
+                .ok_or(Error::InsufficientLiquidity)?;
+            super::mulDiv(_reserveIn, numerator, denominator)
+                .and_then(|amountIn| amountIn.checked_add(1))
+                .ok_or(Error::InsufficientLiquidity)
+        }
+
+        /// Adding new liquidity in the pool
+        /// Returns the amount of share issued for locking given assets
+        #[ink(message)]
+        pub fn provide(
+            &mut self,
+            _amountToken1: Balance,
+            _amountToken2: Balance,
+            _deadline: u64,
+        ) -> Result<Balance, Error> {
+            self.provideWithMin(_amountToken1, _amountToken2, 0, _deadline)
+        }
+
+        /// Adding new liquidity in the pool
+        /// Provide fails if the amount of share issued is less than _minShares or the block timestamp exceeds _deadline
+        #[ink(message)]
+        pub fn provideWithMin(
+            &mut self,
+            _amountToken1: Balance,
+            _amountToken2: Balance,
+            _minShares: Balance,
+            _deadline: u64,
+        ) -> Result<Balance, Error> {
+            self.deadlineCheck(_deadline)?;
+            self.nonReentrant(|dex| {
+                dex.provideWithMinLocked(_amountToken1, _amountToken2, _minShares)
+            })
+        }
+
+        // Body of provideWithMin, executed holding the reentrancy lock
+        fn provideWithMinLocked(
+            &mut self,
+            _amountToken1: Balance,
+            _amountToken2: Balance,
+            _minShares: Balance,
+        ) -> Result<Balance, Error> {
+            self.notPausedCheck()?;
+            self.validTokenAmountCheck(true, _amountToken1)?;
+            self.validTokenAmountCheck(false, _amountToken2)?;
+
+            let genesis = self.totalShares == 0;
+            let share = if genesis {
+                // Genesis liquidity is issued 100 Shares, minus the locked MINIMUM_LIQUIDITY
+                100 * super::PRECISION - super::MINIMUM_LIQUIDITY
+            } else {
+                self.providePriceCheck(_amountToken1, _amountToken2)?;
+
+                let share1 = self.totalShares * _amountToken1 / self.totalToken1;
+                let share2 = self.totalShares * _amountToken2 / self.totalToken2;
+
+                if share1 != share2 {
+                    return Err(Error::NonEquivalentValue);
+                }
+                share1
+            };
+
+            if share == 0 {
+                return Err(Error::ThresholdNotReached);
+            }
+            if share < _minShares {
+                return Err(Error::SlippageExceeded);
+            }
+
+            let caller = self.env().caller();
+            let providerShares = self.shares.get(caller).unwrap_or(0);
+            let isNewProvider = providerShares == 0;
+            if isNewProvider && self.maxProviders != 0 && self.providerCount >= self.maxProviders {
+                return Err(Error::ProviderLimitReached);
+            }
+
+            self.pullToken(true, caller, _amountToken1)?;
+            self.pullToken(false, caller, _amountToken2)?;
+            self.settleFees(caller);
+
+            self.updateCumulativePrices();
+            self.totalToken1 += _amountToken1;
+            self.totalToken2 += _amountToken2;
+            self.principalToken1 += _amountToken1;
+            self.principalToken2 += _amountToken2;
+            if genesis {
+                // Nobody can withdraw the shares held by the pool itself, so totalShares never returns to zero
+                // & a dust genesis followed by a donation cannot round later providers down to zero shares
+                self.shares
+                    .insert(self.env().account_id(), &super::MINIMUM_LIQUIDITY);
+                self.totalShares += super::MINIMUM_LIQUIDITY;
+            }
+            self.totalShares += share;
+            self.shares.insert(caller, &(providerShares + share));
+            if isNewProvider {
+                self.providerCount += 1;
+            }
+            self.recordCheckpoint();
+            self.recordProviderValue(caller);
+            self.recordActiveProvider(caller);
+
+            self.env().emit_event(LiquidityAdded {
+                provider: caller,
+                amountToken1: _amountToken1,
+                amountToken2: _amountToken2,
+                shares: share,
+            });
+            Ok(share)
+        }
+
+        /// Returns the Token1 & Token2 trading fees earned by the caller's shares since it provided liquidity
+        /// Fees are auto-compounded: they are added to the reserves on every swap & paid out by withdraw as
+        /// part of getWithdrawEstimate, so they cannot be claimed separately
+        #[ink(message)]
+        pub fn getClaimableFees(&self) -> (Balance, Balance) {
+            self.getAccruedFees(self.env().caller())
+        }
+
+        /// Returns the amount of shares held by _account
+        #[ink(message)]
+        pub fn shareBalanceOf(&self, _account: AccountId) -> Balance {
+            self.shares.get(_account).unwrap_or(0)
+        }
+
+        /// Returns the amount of _owner's shares that _spender may still transfer
+        #[ink(message)]
+        pub fn shareAllowance(&self, _owner: AccountId, _spender: AccountId) -> Balance {
+            self.shareAllowances.get((_owner, _spender)).unwrap_or(0)
+        }
+
+        /// Transfers _amount of the caller's shares, along with the LP fees they accrued, to _to
+        #[ink(message)]
+        pub fn transferShares(&mut self, _to: AccountId, _amount: Balance) -> Result<(), Error> {
+            let caller = self.env().caller();
+            self.moveShares(caller, _to, _amount)
+        }
+
+        /// Allows _spender to transfer up to _amount of the caller's shares, replacing any previous allowance
+        #[ink(message)]
+        pub fn approveShares(
+            &mut self,
+            _spender: AccountId,
+            _amount: Balance,
+        ) -> Result<(), Error> {
+            let caller = self.env().caller();
+            self.shareAllowances.insert((caller, _spender), &_amount);
+            self.env().emit_event(Approval {
+                owner: caller,
+                spender: _spender,
+                value: _amount,
+            });
+            Ok(())
+        }
+
+        /// Transfers _amount of _from's shares to _to using the allowance _from gave the caller
+        #[ink(message)]
+        pub fn transferSharesFrom(
+            &mut self,
+            _from: AccountId,
+            _to: AccountId,
+            _amount: Balance,
+        ) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let allowance = self.shareAllowance(_from, caller);
+            if _amount > allowance {
+                return Err(Error::InsufficientAllowance);
+            }
+            self.moveShares(_from, _to, _amount)?;
+            self.shareAllowances
+                .insert((_from, caller), &(allowance - _amount));
+            Ok(())
+        }
+
+        /// Returns the estimate of Token1 & Token2 that will be released on burning given _share
+        #[ink(message)]
+        pub fn getWithdrawEstimate(&self, _share: Balance) -> Result<(Balance, Balance), Error> {
+            self.activePool()?;
+            if _share > self.totalShares {
+                return Err(Error::InvalidShare);
+            }
+
+            let amountToken1 = _share * self.totalToken1 / self.totalShares;
+            let amountToken2 = _share * self.totalToken2 / self.totalShares;
+            Ok((amountToken1, amountToken2))
+        }
+
+        /// Returns the Token1 & Token2 rounding dust that sweepDust would release
+        #[ink(message)]
+        pub fn getDust(&self) -> Result<(Balance, Balance), Error> {
+            self.activePool()?;
+            let dustToken1 = self.totalToken1 - self.getOwedReserve(self.totalToken1);
+            let dustToken2 = self.totalToken2 - self.getOwedReserve(self.totalToken2);
+            Ok((dustToken1, dustToken2))
+        }
+
+        /// Releases the rounding dust of the pool reserves to _to. Only callable by the owner
+        /// Safety bound: only the reserve in excess of what keeps the PRECISION scaled value of a share
+        /// unchanged is released, which is always less than totalShares / PRECISION + 1 of each token
+        #[ink(message)]
+        pub fn sweepDust(&mut self, _to: AccountId) -> Result<(Balance, Balance), Error> {
+            self.onlyOwner()?;
+            let (dustToken1, dustToken2) = self.getDust()?;
+
+            self.totalToken1 -= dustToken1;
+            self.totalToken2 -= dustToken2;
+
+            self.pushToken(true, _to, dustToken1)?;
+            self.pushToken(false, _to, dustToken2)?;
+
+            Ok((dustToken1, dustToken2))
+        }
+
+        /// Removes liquidity from the pool and releases corresponding Token1 & Token2 to the withdrawer
+        #[ink(message)]
+        pub fn withdraw(
+            &mut self,
+            _share: Balance,
+            _deadline: u64,
+        ) -> Result<(Balance, Balance), Error> {
+            self.withdrawWithMin(_share, 0, 0, _deadline)
+        }
+
+        /// Burns all of the caller's shares & releases the corresponding Token1 & Token2 to the withdrawer
+        #[ink(message)]
+        pub fn withdrawAll(&mut self, _deadline: u64) -> Result<(Balance, Balance), Error> {
+            let share = self.shares.get(self.env().caller()).unwrap_or(0);
+            if share == 0 {
+                return Err(Error::ZeroLiquidity);
+            }
+            self.withdraw(share, _deadline)
+        }
+
+        /// Removes liquidity from the pool and releases corresponding Token1 & Token2 to the withdrawer
+        /// Withdraw fails if the Token1 or Token2 amount is less than _minToken1 or _minToken2,
+        /// or if the block timestamp exceeds _deadline
+        #[ink(message)]
+        pub fn withdrawWithMin(
+            &mut self,
+            _share: Balance,
+            _minToken1: Balance,
+            _minToken2: Balance,
+            _deadline: u64,
+        ) -> Result<(Balance, Balance), Error> {
+            self.deadlineCheck(_deadline)?;
+            self.nonReentrant(|dex| dex.withdrawWithMinLocked(_share, _minToken1, _minToken2))
+        }
+
+        // Body of withdrawWithMin, executed holding the reentrancy lock
+        fn withdrawWithMinLocked(
+            &mut self,
+            _share: Balance,
+            _minToken1: Balance,
+            _minToken2: Balance,
+        ) -> Result<(Balance, Balance), Error> {
+            self.notPausedCheck()?;
+            let caller = self.env().caller();
+            let callerShares = self.shares.get(caller).unwrap_or(0);
+            self.validAmountCheck(callerShares, _share)?;
+            let remainingShares = callerShares - _share;
+            if remainingShares != 0 && remainingShares < self.minRemainingShares {
+                return Err(Error::DustPositionNotAllowed);
+            }
+
+            let (amountToken1, amountToken2) = self.getWithdrawEstimate(_share)?;
+            if amountToken1 < _minToken1 || amountToken2 < _minToken2 {
+                return Err(Error::SlippageExceeded);
+            }
+            // The withdrawn reserves pay out the fees accrued by the burned shares
+            self.settleFees(caller);
+            let position = self.shares.get(caller).unwrap_or(0);
+            let (accrued1, accrued2) = self.accruedFees.get(caller).unwrap_or((0, 0));
+            self.accruedFees.insert(
+                caller,
+                &(
+                    accrued1 - accrued1 * _share / position,
+                    accrued2 - accrued2 * _share / position,
+                ),
+            );
+            self.principalToken1 -= self.principalToken1 * _share / self.totalShares;
+            self.principalToken2 -= self.principalToken2 * _share / self.totalShares;
+            self.shares.insert(caller, &remainingShares);
+            self.totalShares -= _share;
+            if remainingShares == 0 {
+                self.providerCount -= 1;
+            }
+
+            self.updateCumulativePrices();
+            self.totalToken1 -= amountToken1;
+            self.totalToken2 -= amountToken2;
+            self.recordCheckpoint();
+
+            self.pushToken(true, caller, amountToken1)?;
+            self.pushToken(false, caller, amountToken2)?;
+            self.recordProviderValue(caller);
+
+            self.env().emit_event(LiquidityRemoved {
+                provider: caller,
+                amountToken1,
+                amountToken2,
+                shares: _share,
+            });
+            Ok((amountToken1, amountToken2))
+        }
+
+        /// Returns the amount of Token2 that swapToken1 would send for _amountToken1, net of fees
+        #[ink(message)]
+        pub fn getSwapToken1Estimate(&self, _amountToken1: Balance) -> Result<Balance, Error> {
+            self.getSwapToken1EstimateGivenToken1(_amountToken1)
+        }
+
+        /// Returns the amount of Token1 that swapToken2 would send for _amountToken2, net of fees
+        #[ink(message)]
+        pub fn getSwapToken2Estimate(&self, _amountToken2: Balance) -> Result<Balance, Error> {
+            self.getSwapToken2EstimateGivenToken2(_amountToken2)
+        }
+
+        /// Returns the amount of Token2 that the user will get when swapping a given amount of Token1 for Token2
+        #[ink(message)]
+        pub fn getSwapToken1EstimateGivenToken1(
+            &self,
+            _amountToken1: Balance,
+        ) -> Result<Balance, Error> {
+            self.activePool()?;
+            // Adjusting the fees charged
+            let feeBps = self.getFeeBpsForAmountIn(self.totalToken1, _amountToken1);
+            let _amountToken1 = super::mulDiv(_amountToken1, super::BPS - feeBps, super::BPS)
+                .ok_or(Error::InsufficientLiquidity)?;
+
+            if self.isWeighted() {
+                return self.getWeightedAmountOut(
+                    _amountToken1,
+                    self.totalToken1,
+                    self.totalToken2,
+                    self.weightToken1,
+                    self.weightToken2,
+                );
+            }
+
+            let token1After = self
+                .totalToken1
+                .checked_add(_amountToken1)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let token2After = super::mulDiv(self.totalToken1, self.totalToken2, token1After)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let mut amountToken2 = self.totalToken2 - token2After;
+
+            // To ensure that Token2's pool is not completely depleted leading to inf:0 ratio
+            if amountToken2 == self.totalToken2 {
+                amountToken2 -= 1;
+            }
+            Ok(amountToken2)
+        }
+
+        /// Returns the amount of Token1 that the user should swap to get _amountToken2 in return
+        #[ink(message)]
+        pub fn getSwapToken1EstimateGivenToken2(
+            &self,
+            _amountToken2: Balance,
+        ) -> Result<Balance, Error> {
+            self.activePool()?;
+            if _amountToken2 >= self.totalToken2 {
+                return Err(Error::InsufficientLiquidity);
+            }
+            let feeBps = self.getFeeBpsForAmountOut(self.totalToken2, _amountToken2);
+
+            if self.isWeighted() {
+                let amountToken1 = self.getWeightedAmountIn(
+                    _amountToken2,
+                    self.totalToken1,
+                    self.totalToken2,
+                    self.weightToken1,
+                    self.weightToken2,
+                )?;
+                return super::mulDiv(amountToken1, super::BPS, super::BPS - feeBps)
+                    .ok_or(Error::InsufficientLiquidity);
+            }
+
+            let token2After = self.totalToken2 - _amountToken2;
+            let token1After = super::mulDiv(self.totalToken1, self.totalToken2, token2After)
+                .ok_or(Error::InsufficientLiquidity)?;
+            super::mulDiv(
+                token1After - self.totalToken1,
+                super::BPS,
+                super::BPS - feeBps,
+            )
+            .ok_or(Error::InsufficientLiquidity)
+        }
+
+        /// Returns the Token2 received for selling _amountIn Token1 & the Token1 received for selling _amountIn Token2
+        /// Both estimates include fees and are computed against the current reserves
+        #[ink(message)]
+        pub fn previewBothDirections(
+            &self,
+            _amountIn: Balance,
+        ) -> Result<(Balance, Balance), Error> {
+            let amountToken2 = self.getSwapToken1EstimateGivenToken1(_amountIn)?;
+            let amountToken1 = self.getSwapToken2EstimateGivenToken2(_amountIn)?;
+            Ok((amountToken2, amountToken1))
+        }
+
+        /// Swaps given amount of Token1 (_token1In) or Token2 for the other token and sends the output to _to
+        /// Swap fails if the output is less than _minOut, if _to differs from the caller and is on the deny list
+        /// or if the block timestamp exceeds _deadline
+        #[ink(message)]
+        pub fn swapTo(
+            &mut self,
+            _token1In: bool,
+            _amountIn: Balance,
+            _minOut: Balance,
+            _to: AccountId,
+            _deadline: u64,
+        ) -> Result<SwapResult, Error> {
+            self.deadlineCheck(_deadline)?;
+            let caller = self.env().caller();
+            if _to != caller && self.isRecipientDenied(_to) {
+                return Err(Error::ComplianceRejected);
+            }
+            self.swapOutCooldownCheck(_to)?;
+
+            self.nonReentrant(|dex| {
+                if _token1In {
+                    dex.swapToken1GivenToken1To(_amountIn, _minOut, _to)
+                } else {
+                    dex.swapToken2GivenToken2To(_amountIn, _minOut, _to)
+                }
+            })
+        }
+
+        /// Returns _samples evenly spaced (input, output) points from zero to _maxInput of the swap curve
+        /// selling Token1 (_token1In) or Token2, for charting slippage. _samples is capped at MAX_CURVE_SAMPLES
+        #[ink(message)]
+        pub fn sampleSwapCurve(
+            &self,
+            _token1In: bool,
+            _samples: u8,
+            _maxInput: Balance,
+        ) -> Result<Vec<(Balance, Balance)>, Error> {
+            self.activePool()?;
+            let samples = _samples.min(super::MAX_CURVE_SAMPLES) as Balance;
+            let intervals = if samples > 1 { samples - 1 } else { 1 };
+
+            let mut curve = Vec::new();
+            for i in 0..samples {
+                let input = _maxInput * i / intervals;
+                let output = if _token1In {
+                    self.getSwapToken1EstimateGivenToken1(input)?
+                } else {
+                    self.getSwapToken2EstimateGivenToken2(input)?
+                };
+                curve.push((input, output));
+            }
+            Ok(curve)
+        }
+
+        /// Executes the given swaps in sequence against the evolving reserves and returns their results
+        /// The whole batch fails, reverting the legs already executed, if any leg's output is below its minOut
+        /// or if the block timestamp exceeds _deadline
+        #[ink(message)]
+        pub fn batchSwap(
+            &mut self,
+            _swaps: Vec<SwapOrder>,
+            _deadline: u64,
+        ) -> Result<Vec<SwapResult>, Error> {
+            self.deadlineCheck(_deadline)?;
+            self.activePool()?;
+            // The legs pay out to the caller within the same call, so the batch is held to the cooldown once
+            let caller = self.env().caller();
+            self.swapOutCooldownCheck(caller)?;
+
+            let mut results = Vec::new();
+            for order in _swaps {
+                let result = self.nonReentrant(|dex| {
+                    if order.token1In {
+                        dex.swapToken1GivenToken1To(order.amountIn, order.minOut, caller)
+                    } else {
+                        dex.swapToken2GivenToken2To(order.amountIn, order.minOut, caller)
+                    }
+                })?;
+                results.push(result);
+            }
+            Ok(results)
+        }
+
+        /// Swaps _amountIn of Token1 of _poolA for Token1 of _poolB through the bridge token backing Token2 of both
+        /// pools & returns the amount sent to the caller, who must have approved this contract for _amountIn
+        /// Swap fails, reverting both legs, if either leg fails, if the output is less than _minOut
+        /// or if the block timestamp exceeds _deadline
+        #[ink(message)]
+        pub fn swapExactToken1ForToken1ViaBridge(
+            &mut self,
+            _poolA: AccountId,
+            _poolB: AccountId,
+            _amountIn: Balance,
+            _minOut: Balance,
+            _deadline: u64,
+        ) -> Result<Balance, Error> {
+            self.deadlineCheck(_deadline)?;
+            let (tokenIn, bridgeA) = self.queryPoolTokens(_poolA)?;
+            let (_, bridgeB) = self.queryPoolTokens(_poolB)?;
+            let (tokenIn, bridge) = match (tokenIn, bridgeA) {
+                (Some(tokenIn), Some(bridge)) if bridgeB == Some(bridge) => (tokenIn, bridge),
+                _ => return Err(Error::InvalidRoute),
+            };
+            let caller = self.env().caller();
+            let router = self.env().account_id();
+
+            // Token1 of _poolA for the bridge token, held by this contract in between the legs
+            self.transferTokenFrom(tokenIn, caller, router, _amountIn)?;
+            self.approveToken(tokenIn, _poolA, _amountIn)?;
+            let amountBridge = self
+                .callPoolSwap(_poolA, true, _amountIn, 0, router, _deadline)?
+                .amountOut;
+
+            // The bridge token for Token1 of _poolB, sent to the caller
+            self.approveToken(bridge, _poolB, amountBridge)?;
+            let result =
+                self.callPoolSwap(_poolB, false, amountBridge, _minOut, caller, _deadline)?;
+            Ok(result.amountOut)
+        }
+
+        /// Returns the minimum Token2 accepted for swapping _amountToken1 at the default slippage tolerance
+        #[ink(message)]
+        pub fn getDefaultMinToken2(&self, _amountToken1: Balance) -> Result<Balance, Error> {
+            let amountToken2 = self.getSwapToken1EstimateGivenToken1(_amountToken1)?;
+            Ok(self.applyDefaultSlippage(amountToken2))
+        }
+
+        // Reduces a quoted output by the default slippage tolerance
+        fn applyDefaultSlippage(&self, _quote: Balance) -> Balance {
+            _quote * (super::BPS - self.defaultSlippageBps) / super::BPS
+        }
+
+        /// Swaps given amount of Token1 to Token2 accepting the pool's default slippage tolerance
+        /// The tolerance is applied to _quotedToken2, a Token2 estimate the caller obtained before sending the transaction
+        /// Swap fails if Token2 amount is less than _quotedToken2 minus the default tolerance
+        #[ink(message)]
+        pub fn swapToken1WithDefaultSlippage(
+            &mut self,
+            _amountToken1: Balance,
+            _quotedToken2: Balance,
+            _deadline: u64,
+        ) -> Result<SwapResult, Error> {
+            let minToken2 = self.applyDefaultSlippage(_quotedToken2);
+            self.swapToken1GivenToken1(_amountToken1, minToken2, _deadline)
+        }
+
+        /// Swaps _amountToken1 of Token1 for Token2 & returns the amount of Token2 sent to the caller
+        /// Swap fails if Token2 amount is less than _minToken2
+        #[ink(message)]
+        pub fn swapToken1(
+            &mut self,
+            _amountToken1: Balance,
+            _minToken2: Balance,
+            _deadline: u64,
+        ) -> Result<SwapResult, Error> {
+            self.swapToken1GivenToken1(_amountToken1, _minToken2, _deadline)
+        }
+
+        /// Swaps given amount of Token1 to Token2 using algorithmic price determination
+        /// Swap fails if Token2 amount is less than _minToken2 or the block timestamp exceeds _deadline
+        #[ink(message)]
+        pub fn swapToken1GivenToken1(
+            &mut self,
+            _amountToken1: Balance,
+            _minToken2: Balance,
+            _deadline: u64,
+        ) -> Result<SwapResult, Error> {
+            self.deadlineCheck(_deadline)?;
+            let caller = self.env().caller();
+            self.swapOutCooldownCheck(caller)?;
+            self.nonReentrant(|dex| dex.swapToken1GivenToken1To(_amountToken1, _minToken2, caller))
+        }
+
+        // Swaps given amount of Token1 to Token2 & sends the Token2 to _to
+        // Callers must check the swap out cooldown of _to
+        fn swapToken1GivenToken1To(
+            &mut self,
+            _amountToken1: Balance,
+            _minToken2: Balance,
+            _to: AccountId,
+        ) -> Result<SwapResult, Error> {
+            let caller = self.env().caller();
+            self.swapEnabledCheck()?;
+            self.validTokenAmountCheck(true, _amountToken1)?;
+
+            let amountToken2 = self.getSwapToken1EstimateGivenToken1(_amountToken1)?;
+            if amountToken2 < _minToken2 {
+                return Err(Error::SlippageExceeded);
+            }
+            let feeBps = self.getFeeBpsForAmountIn(self.totalToken1, _amountToken1);
+            self.dustSwapCheck(true, _amountToken1, amountToken2, feeBps)?;
+            self.pullToken(true, caller, _amountToken1)?;
+
+            let spotBefore = self.getSpotPrice(true);
+            let reserveIn = self.takeProtocolFee(_amountToken1, true, feeBps)?;
+            self.recordVolume(_amountToken1);
+            self.updateCumulativePrices();
+            self.totalToken1 += reserveIn;
+            self.totalToken2 -= amountToken2;
+
+            self.pushToken(false, _to, amountToken2)?;
+            self.lastSwapOutBlock
+                .insert(_to, &self.env().block_number());
+            self.tradeCount += 1;
+            self.recordCheckpoint();
+
+            self.env().emit_event(Swapped {
+                trader: caller,
+                token1In: true,
+                amountIn: _amountToken1,
+                amountOut: amountToken2,
+            });
+            Ok(self.getSwapResult(_amountToken1, amountToken2, feeBps, spotBefore))
+        }
+
+        /// Swaps given amount of Token1 to Token2 using algorithmic price determination
+        /// Swap fails if amount of Token1 required to obtain _amountToken2 exceeds _maxToken1
+        /// or if the block timestamp exceeds _deadline
+        #[ink(message)]
+        pub fn swapToken1GivenToken2(
+            &mut self,
+            _amountToken2: Balance,
+            _maxToken1: Balance,
+            _deadline: u64,
+        ) -> Result<SwapResult, Error> {
+            self.deadlineCheck(_deadline)?;
+            self.nonReentrant(|dex| dex.swapToken1GivenToken2Locked(_amountToken2, _maxToken1))
+        }
+
+        // Body of swapToken1GivenToken2, executed holding the reentrancy lock
+        fn swapToken1GivenToken2Locked(
+            &mut self,
+            _amountToken2: Balance,
+            _maxToken1: Balance,
+        ) -> Result<SwapResult, Error> {
+            let caller = self.env().caller();
+            self.swapEnabledCheck()?;
+            self.swapOutCooldownCheck(caller)?;
+            let amountToken1 = self.getSwapToken1EstimateGivenToken2(_amountToken2)?;
+            if amountToken1 > _maxToken1 {
+                return Err(Error::SlippageExceeded);
+            }
+            self.validTokenAmountCheck(true, amountToken1)?;
+
+            let feeBps = self.getFeeBpsForAmountOut(self.totalToken2, _amountToken2);
+            self.dustSwapCheck(true, amountToken1, _amountToken2, feeBps)?;
+            self.pullToken(true, caller, amountToken1)?;
+
+            let spotBefore = self.getSpotPrice(true);
+            let reserveIn = self.takeProtocolFee(amountToken1, true, feeBps)?;
+            self.recordVolume(amountToken1);
+            self.updateCumulativePrices();
+            self.totalToken1 += reserveIn;
+            self.totalToken2 -= _amountToken2;
+
+            self.pushToken(false, caller, _amountToken2)?;
+            self.lastSwapOutBlock
+                .insert(caller, &self.env().block_number());
+            self.tradeCount += 1;
+            self.recordCheckpoint();
+
+            self.env().emit_event(Swapped {
+                trader: caller,
+                token1In: true,
+                amountIn: amountToken1,
+                amountOut: _amountToken2,
+            });
+            Ok(self.getSwapResult(amountToken1, _amountToken2, feeBps, spotBefore))
+        }
+
+        /// Returns the amount of Token2 that the user will get when swapping a given amount of Token1 for Token2
+        #[ink(message)]
+        pub fn getSwapToken2EstimateGivenToken2(
+            &self,
+            _amountToken2: Balance,
+        ) -> Result<Balance, Error> {
+            self.activePool()?;
+            // Adjusting the fees charged
+            let feeBps = self.getFeeBpsForAmountIn(self.totalToken2, _amountToken2);
+            let _amountToken2 = super::mulDiv(_amountToken2, super::BPS - feeBps, super::BPS)
+                .ok_or(Error::InsufficientLiquidity)?;
+
+            if self.isWeighted() {
+                return self.getWeightedAmountOut(
+                    _amountToken2,
+                    self.totalToken2,
+                    self.totalToken1,
+                    self.weightToken2,
+                    self.weightToken1,
+                );
+            }
+
+            let token2After = self
+                .totalToken2
+                .checked_add(_amountToken2)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let token1After = super::mulDiv(self.totalToken1, self.totalToken2, token2After)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let mut amountToken1 = self.totalToken1 - token1After;
+
+            // To ensure that Token1's pool is not completely depleted leading to inf:0 ratio
+            if amountToken1 == self.totalToken1 {
+                amountToken1 -= 1;
+            }
+            Ok(amountToken1)
+        }
+
+        /// Returns the amount of Token2 that the user should swap to get _amountToken1 in return
+        #[ink(message)]
+        pub fn getSwapToken2EstimateGivenToken1(
+            &self,
+            _amountToken1: Balance,
+        ) -> Result<Balance, Error> {
+            self.activePool()?;
+            if _amountToken1 >= self.totalToken1 {
+                return Err(Error::InsufficientLiquidity);
+            }
+            let feeBps = self.getFeeBpsForAmountOut(self.totalToken1, _amountToken1);
+
+            if self.isWeighted() {
+                let amountToken2 = self.getWeightedAmountIn(
+                    _amountToken1,
+                    self.totalToken2,
+                    self.totalToken1,
+                    self.weightToken2,
+                    self.weightToken1,
+                )?;
+                return super::mulDiv(amountToken2, super::BPS, super::BPS - feeBps)
+                    .ok_or(Error::InsufficientLiquidity);
+            }
+
+            let token1After = self.totalToken1 - _amountToken1;
+            let token2After = super::mulDiv(self.totalToken1, self.totalToken2, token1After)
+                .ok_or(Error::InsufficientLiquidity)?;
+            super::mulDiv(
+                token2After - self.totalToken2,
+                super::BPS,
+                super::BPS - feeBps,
+            )
+            .ok_or(Error::InsufficientLiquidity)
+        }
+
+        /// Sends _amountToken1Out of Token1 & _amountToken2Out of Token2 to _callback, then calls its
+        /// onFlashSwap(initiator, amountToken1Out, amountToken2Out) -> (amountToken1In, amountToken2In) handler
+        /// & pulls the returned amounts back from _callback, which must hold & have approved them
+        /// Flash swap fails with InsufficientLiquidity, reverting the transfers, if the reserves net of the
+        /// swap fee on the repaid amounts fall below the constant product. Not supported by weighted pools
+        #[ink(message)]
+        pub fn flashSwap(
+            &mut self,
+            _amountToken1Out: Balance,
+            _amountToken2Out: Balance,
+            _callback: AccountId,
+        ) -> Result<(), Error> {
+            self.nonReentrant(|dex| {
+                dex.flashSwapLocked(_amountToken1Out, _amountToken2Out, _callback)
+            })
+        }
+
+        // Body of flashSwap, executed holding the reentrancy lock
+        fn flashSwapLocked(
+            &mut self,
+            _amountToken1Out: Balance,
+            _amountToken2Out: Balance,
+            _callback: AccountId,
+        ) -> Result<(), Error> {
+            let caller = self.env().caller();
+            self.swapEnabledCheck()?;
+            if self.isWeighted() {
+                return Err(Error::UnsupportedPool);
+            }
+            if _amountToken1Out == 0 && _amountToken2Out == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            if _amountToken1Out >= self.totalToken1 || _amountToken2Out >= self.totalToken2 {
+                return Err(Error::InsufficientLiquidity);
+            }
+
+            self.pushToken(true, _callback, _amountToken1Out)?;
+            self.pushToken(false, _callback, _amountToken2Out)?;
+            let (amountToken1In, amountToken2In) =
+                self.callFlashSwapHandler(_callback, _amountToken1Out, _amountToken2Out)?;
+            self.pullToken(true, _callback, amountToken1In)?;
+            self.pullToken(false, _callback, amountToken2In)?;
+
+            // The repaid amounts net of the swap fee must keep the constant product
+            let feeBpsToken1 = self.getFeeBpsForAmountIn(self.totalToken1, amountToken1In);
+            let feeBpsToken2 = self.getFeeBpsForAmountIn(self.totalToken2, amountToken2In);
+            let netToken1 = super::mulDiv(amountToken1In, super::BPS - feeBpsToken1, super::BPS)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let netToken2 = super::mulDiv(amountToken2In, super::BPS - feeBpsToken2, super::BPS)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let token1After = (self.totalToken1 - _amountToken1Out)
+                .checked_add(netToken1)
+                .ok_or(Error::InsufficientLiquidity)?;
+            let token2After = (self.totalToken2 - _amountToken2Out)
+                .checked_add(netToken2)
+                .ok_or(Error::InsufficientLiquidity)?;
+            // token1After * token2After >= totalToken1 * totalToken2, a product too large for a Balance passes
+            if super::mulDiv(token1After, token2After, self.totalToken1)
+                .is_some_and(|product| product < self.totalToken2)
+            {
+                return Err(Error::InsufficientLiquidity);
+            }
+
+            let reserveInToken1 = self.takeProtocolFee(amountToken1In, true, feeBpsToken1)?;
+            let reserveInToken2 = self.takeProtocolFee(amountToken2In, false, feeBpsToken2)?;
+            self.updateCumulativePrices();
+            self.totalToken1 = self.totalToken1 - _amountToken1Out + reserveInToken1;
+            self.totalToken2 = self.totalToken2 - _amountToken2Out + reserveInToken2;
+            self.tradeCount += 1;
+            self.recordCheckpoint();
+
+            self.env().emit_event(FlashSwap {
+                trader: caller,
+                callback: _callback,
+                amountToken1Out: _amountToken1Out,
+                amountToken2Out: _amountToken2Out,
+                amountToken1In,
+                amountToken2In,
+            });
+            Ok(())
+        }
+
+        /// Swaps _amountToken2 of Token2 for Token1 & returns the amount of Token1 sent to the caller
+        /// Swap fails if Token1 amount is less than _minToken1
+        #[ink(message)]
+        pub fn swapToken2(
+            &mut self,
+            _amountToken2: Balance,
+            _minToken1: Balance,
+            _deadline: u64,
+        ) -> Result<SwapResult, Error> {
+            self.swapToken2GivenToken2(_amountToken2, _minToken1, _deadline)
+        }
+
+        /// Swaps given amount of Token2 to Token1 using algorithmic price determination
+        /// Swap fails if Token1 amount is less than _minToken1 or the block timestamp exceeds _deadline
+        #[ink(message)]
+        pub fn swapToken2GivenToken2(
+            &mut self,
+            _amountToken2: Balance,
+            _minToken1: Balance,
+            _deadline: u64,
+        ) -> Result<SwapResult, Error> {
+            self.deadlineCheck(_deadline)?;
+            let caller = self.env().caller();
+            self.swapOutCooldownCheck(caller)?;
+            self.nonReentrant(|dex| dex.swapToken2GivenToken2To(_amountToken2, _minToken1, caller))
+        }
+
+        // Swaps given amount of Token2 to Token1 & sends the Token1 to _to
+        // Callers must check the swap out cooldown of _to
+        fn swapToken2GivenToken2To(
+            &mut self,
+            _amountToken2: Balance,
+            _minToken1: Balance,
+            _to: AccountId,
+        ) -> Result<SwapResult, Error> {
+            let caller = self.env().caller();
+            self.swapEnabledCheck()?;
+            self.validTokenAmountCheck(false, _amountToken2)?;
+
+            let amountToken1 = self.getSwapToken2EstimateGivenToken2(_amountToken2)?;
+            if amountToken1 < _minToken1 {
+                return Err(Error::SlippageExceeded);
+            }
+            let feeBps = self.getFeeBpsForAmountIn(self.totalToken2, _amountToken2);
+            self.dustSwapCheck(false, _amountToken2, amountToken1, feeBps)?;
+            self.pullToken(false, caller, _amountToken2)?;
+
+            let spotBefore = self.getSpotPrice(true);
+            let reserveIn = self.takeProtocolFee(_amountToken2, false, feeBps)?;
+            self.recordVolume(_amountToken2);
+            self.updateCumulativePrices();
+            self.totalToken2 += reserveIn;
+            self.totalToken1 -= amountToken1;
+
+            self.pushToken(true, _to, amountToken1)?;
+            self.lastSwapOutBlock
+                .insert(_to, &self.env().block_number());
+            self.tradeCount += 1;
+            self.recordCheckpoint();
+
+            self.env().emit_event(Swapped {
+                trader: caller,
+                token1In: false,
+                amountIn: _amountToken2,
+                amountOut: amountToken1,
+            });
+            Ok(self.getSwapResult(_amountToken2, amountToken1, feeBps, spotBefore))
+        }
+
+        /// Swaps given amount of Token2 to Token1 using algorithmic price determination
+        /// Swap fails if amount of Token2 required to obtain _amountToken1 exceeds _maxToken2
+        /// or if the block timestamp exceeds _deadline
+        #[ink(message)]
+        pub fn swapToken2GivenToken1(
+            &mut self,
+            _amountToken1: Balance,
+            _maxToken2: Balance,
+            _deadline: u64,
+        ) -> Result<SwapResult, Error> {
+            self.deadlineCheck(_deadline)?;
+            self.nonReentrant(|dex| dex.swapToken2GivenToken1Locked(_amountToken1, _maxToken2))
+        }
+
+        // Body of swapToken2GivenToken1, executed holding the reentrancy lock
+        fn swapToken2GivenToken1Locked(
+            &mut self,
+            _amountToken1: Balance,
+            _maxToken2: Balance,
+        ) -> Result<SwapResult, Error> {
+            let caller = self.env().caller();
+            self.swapEnabledCheck()?;
+            self.swapOutCooldownCheck(caller)?;
+            let amountToken2 = self.getSwapToken2EstimateGivenToken1(_amountToken1)?;
+            if amountToken2 > _maxToken2 {
+                return Err(Error::SlippageExceeded);
+            }
+            self.validTokenAmountCheck(false, amountToken2)?;
+
+            let feeBps = self.getFeeBpsForAmountOut(self.totalToken1, _amountToken1);
+            self.dustSwapCheck(false, amountToken2, _amountToken1, feeBps)?;
+            self.pullToken(false, caller, amountToken2)?;
+
+            let spotBefore = self.getSpotPrice(true);
+            let reserveIn = self.takeProtocolFee(amountToken2, false, feeBps)?;
+            self.recordVolume(amountToken2);
+            self.updateCumulativePrices();
+            self.totalToken2 += reserveIn;
+            self.totalToken1 -= _amountToken1;
+
+            self.pushToken(true, caller, _amountToken1)?;
+            self.lastSwapOutBlock
+                .insert(caller, &self.env().block_number());
+            self.tradeCount += 1;
+            self.recordCheckpoint();
+
+            self.env().emit_event(Swapped {
+                trader: caller,
+                token1In: false,
+                amountIn: amountToken2,
+                amountOut: _amountToken1,
+            });
+            Ok(self.getSwapResult(amountToken2, _amountToken1, feeBps, spotBefore))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[ink::test]
+        fn min_shares_for_amounts_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000, 2_000).unwrap();
+
+            // Empty pool returns the genesis shares
+            let genesis = dex.getMinSharesForAmounts(100, 200).unwrap();
+            assert_eq!(genesis, 100 * crate::PRECISION - crate::MINIMUM_LIQUIDITY);
+            assert_eq!(dex.provide(100, 200, u64::MAX), Ok(genesis));
+
+            let minShares = dex.getMinSharesForAmounts(50, 100).unwrap();
+            let share = dex.provide(50, 100, u64::MAX).unwrap();
+            assert!(share >= minShares);
+        }
+
+        #[ink::test]
+        fn provide_rejects_stale_ratio() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 2_000, u64::MAX).is_ok());
+
+            assert_eq!(dex.setMaxProvideDeviation(100), Ok(()));
+            assert_eq!(dex.getMaxProvideDeviation(), 100);
+
+            // Providing at a 1:1 ratio deviates 50% from the 1:2 spot
+            assert_eq!(
+                dex.provide(1_000, 1_000, u64::MAX),
+                Err(Error::NonEquivalentValue)
+            );
+
+            // 18 decimal reserves whose cross products exceed u128
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10u128.pow(24), 10u128.pow(24)).unwrap();
+            assert!(dex
+                .provide(10u128.pow(22), 10u128.pow(20), u64::MAX)
+                .is_ok());
+            assert!(dex
+                .provide(10u128.pow(22), 10u128.pow(20), u64::MAX)
+                .is_ok());
+            assert_eq!(
+                dex.provide(10u128.pow(22), 2 * 10u128.pow(20), u64::MAX),
+                Err(Error::NonEquivalentValue)
+            );
+        }
+
+        #[ink::test]
+        fn set_max_provide_deviation_only_owner() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.setMaxProvideDeviation(100), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn validate_path_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+
+            assert_eq!(dex.validatePath(vec![true]), Ok(()));
+            assert_eq!(dex.validatePath(vec![false]), Ok(()));
+            assert_eq!(
+                dex.validatePath(vec![false, true, false]),
+                Err(Error::InvalidPath)
+            );
+            assert_eq!(dex.validatePath(vec![]), Err(Error::InvalidPath));
+            assert_eq!(dex.validatePath(vec![true, true]), Err(Error::InvalidPath));
+            assert_eq!(dex.validatePath(vec![true, false]), Err(Error::InvalidPath));
+            assert_eq!(
+                dex.validatePath(vec![true, false, true, false, true]),
+                Err(Error::InvalidPath)
+            );
+
+            let amounts = dex.getAmountsOut(100, vec![true]).unwrap();
+            assert_eq!(
+                amounts,
+                vec![100, dex.getSwapToken1EstimateGivenToken1(100).unwrap()]
+            );
+            assert_eq!(
+                dex.getAmountsOut(100, vec![true, false, true, false, true]),
+                Err(Error::InvalidPath)
+            );
+        }
+
+        #[ink::test]
+        fn sweep_dust_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(2_000_000, 2_000_000).unwrap();
+            assert!(dex.provide(1_000_000, 1_000_000, u64::MAX).is_ok());
+
+            for _ in 0..5 {
+                assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            }
+
+            let shareValue = dex.getWithdrawEstimate(crate::PRECISION).unwrap();
+            assert_eq!(dex.getDust(), Ok((0, 35)));
+            assert_eq!(dex.sweepDust(accounts.django), Ok((0, 35)));
+
+            // Remaining providers keep their share value and nothing is left to sweep
+            assert_eq!(dex.getWithdrawEstimate(crate::PRECISION), Ok(shareValue));
+            assert_eq!(dex.getDust(), Ok((0, 0)));
+
+            ink::env::test::set_caller::<Environment>(accounts.django);
+            assert_eq!(dex.getMyHoldings(), (0, 35, 0));
+            assert_eq!(dex.sweepDust(accounts.django), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn max_providers_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.setMaxProviders(2), Ok(()));
+
+            for provider in [accounts.alice, accounts.bob, accounts.charlie] {
+                ink::env::test::set_caller::<Environment>(provider);
+                dex.faucet(10_000, 10_000).unwrap();
+            }
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+            assert_eq!(dex.getProviderLimit(), (2, 2));
+
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(
+                dex.provide(1_000, 1_000, u64::MAX),
+                Err(Error::ProviderLimitReached)
+            );
+
+            // Existing providers can still top up
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+            assert_eq!(dex.getProviderLimit(), (2, 2));
+        }
+
+        #[ink::test]
+        fn bid_ask_spread_widens_with_fee() {
+            let mut low = Dex::new(1);
+            assert_eq!(low.setFaucetEnabled(true), Ok(()));
+            assert_eq!(low.getBidAsk(), Err(Error::ZeroLiquidity));
+            low.faucet(10_000, 10_000).unwrap();
+            assert!(low.provide(1_000, 2_000, u64::MAX).is_ok());
+
+            let mut high = Dex::new(30);
+            assert_eq!(high.setFaucetEnabled(true), Ok(()));
+            high.faucet(10_000, 10_000).unwrap();
+            assert!(high.provide(1_000, 2_000, u64::MAX).is_ok());
+
+            let (lowBid, lowAsk) = low.getBidAsk().unwrap();
+            let (highBid, highAsk) = high.getBidAsk().unwrap();
+            assert!(lowBid < 2 * crate::PRECISION && 2 * crate::PRECISION < lowAsk);
+            assert!(highAsk - highBid > lowAsk - lowBid);
+
+            // Reserves whose PRECISION scaled weighted value exceeds u128
+            let mut large = Dex::new(1);
+            assert_eq!(large.setFaucetEnabled(true), Ok(()));
+            large.faucet(10u128.pow(32), 10u128.pow(32)).unwrap();
+            assert!(large
+                .provide(10u128.pow(31), 2 * 10u128.pow(31), u64::MAX)
+                .is_ok());
+            assert_eq!(large.getBidAsk(), Ok((lowBid, lowAsk)));
+        }
+
+        #[ink::test]
+        fn min_liquidity_for_swaps_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.setMinLiquidityForSwaps(1_000_000), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+
+            // Providing works below the threshold but swaps are blocked
+            assert!(dex.provide(100, 100, u64::MAX).is_ok());
+            assert_eq!(
+                dex.swapToken1GivenToken1(10, 0, u64::MAX),
+                Err(Error::InsufficientLiquidity)
+            );
+            assert_eq!(
+                dex.swapToken2GivenToken2(10, 0, u64::MAX),
+                Err(Error::InsufficientLiquidity)
+            );
+
+            assert!(dex.provide(5_000, 5_000, u64::MAX).is_ok());
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+        }
+
+        #[ink::test]
+        fn pool_age_metrics_work() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+            assert_eq!(dex.getPoolAge(), 0);
+
+            for _ in 0..4 {
+                ink::env::test::advance_block::<Environment>();
+            }
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            assert!(dex.swapToken2GivenToken2(1_000, 0, u64::MAX).is_ok());
+
+            // Two swaps over four blocks
+            assert_eq!(dex.getPoolAge(), 4);
+            assert_eq!(dex.getSwapsPerBlock(), crate::PRECISION / 2);
+        }
+
+        #[ink::test]
+        fn preview_both_directions_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.previewBothDirections(10), Err(Error::ZeroLiquidity));
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.provide(10_000, 40_000, u64::MAX).is_ok());
+
+            let (amountToken2, amountToken1) = dex.previewBothDirections(100).unwrap();
+            assert_eq!(
+                amountToken2,
+                dex.getSwapToken1EstimateGivenToken1(100).unwrap()
+            );
+            assert_eq!(
+                amountToken1,
+                dex.getSwapToken2EstimateGivenToken2(100).unwrap()
+            );
+
+            // Token1 is the scarce side, so selling it yields more than buying it back
+            assert!(amountToken2 > amountToken1);
+            assert_eq!(
+                dex.getPoolDetails(),
+                (10_000, 40_000, 100 * crate::PRECISION, 3)
+            );
+        }
+
+        #[ink::test]
+        fn weighted_pool_works() {
+            let mut constant = Dex::new(0);
+            assert_eq!(constant.setFaucetEnabled(true), Ok(()));
+            assert_eq!(constant.getWeights(), (50, 50));
+            constant.faucet(2_000_000, 2_000_000).unwrap();
+            assert!(constant.provide(1_000_000, 1_000_000, u64::MAX).is_ok());
+
+            let mut weighted = Dex::newWeighted(0, 80);
+            assert_eq!(weighted.setFaucetEnabled(true), Ok(()));
+            assert_eq!(weighted.getWeights(), (80, 20));
+            weighted.faucet(2_000_000, 2_000_000).unwrap();
+            assert!(weighted.provide(1_000_000, 1_000_000, u64::MAX).is_ok());
+
+            // With equal reserves an 80/20 pool prices Token1 at roughly 4 Token2
+            let constantOut = constant.getSwapToken1EstimateGivenToken1(1_000).unwrap();
+            let weightedOut = weighted.getSwapToken1EstimateGivenToken1(1_000).unwrap();
+            assert!(weightedOut > 3 * constantOut);
+
+            // Selling Token2 into the heavier side releases less Token1
+            let weightedBack = weighted.getSwapToken2EstimateGivenToken2(1_000).unwrap();
+            assert!(weightedBack < constantOut);
+        }
+
+        #[ink::test]
+        fn weighted_pool_prices_small_swaps() {
+            let reserve = 1_000_000_000_000_000_000_000_000;
+            let mut weighted = Dex::newWeighted(0, 80);
+            assert_eq!(weighted.setFaucetEnabled(true), Ok(()));
+            weighted.faucet(reserve, reserve).unwrap();
+            assert!(weighted.provide(reserve, reserve, u64::MAX).is_ok());
+
+            // Token1 is worth 4 Token2 at the margin, so no swap may pay out more than that
+            let amountOut = weighted.getSwapToken1EstimateGivenToken1(1_000).unwrap();
+            assert!(amountOut <= 4_000);
+            let amountIn = 1_000_000_000_000_000_000;
+            let amountOut = weighted.getSwapToken1EstimateGivenToken1(amountIn).unwrap();
+            assert!(amountOut <= 4 * amountIn);
+            assert!(amountOut > 4 * amountIn / 1_000_000 * 999_997);
+            let required = weighted.getSwapToken2EstimateGivenToken1(1_000).unwrap();
+            assert!(required >= 4_000);
+
+            // Co-prime weights are priced at their 51 / 49 ratio
+            let mut coprime = Dex::newWeighted(0, 51);
+            assert_eq!(coprime.setFaucetEnabled(true), Ok(()));
+            coprime.faucet(reserve, reserve).unwrap();
+            assert!(coprime.provide(reserve, reserve, u64::MAX).is_ok());
+            let amountOut = coprime.getSwapToken1EstimateGivenToken1(amountIn).unwrap();
+            assert!(amountOut <= amountIn * 51 / 49);
+            assert!(amountOut > amountIn * 51 / 49 / 1_000_000 * 999_997);
+            let required = coprime.getSwapToken1EstimateGivenToken2(amountIn).unwrap();
+            assert!(required >= amountIn * 49 / 51);
+            assert!(required < amountIn * 49 / 51 / 1_000_000 * 1_000_003);
+        }
+
+        #[ink::test]
+        fn dynamic_fee_rises_with_volatility() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.getSwapFeeBps(), 30);
+            assert_eq!(
+                dex.setDynamicFee(true, 50, 10),
+                Err(Error::InvalidFeeBounds)
+            );
+            assert_eq!(dex.setDynamicFee(true, 10, 100), Ok(()));
+            dex.faucet(100_000_000, 100_000_000).unwrap();
+            assert!(dex.provide(10_000_000, 10_000_000, u64::MAX).is_ok());
+
+            // Calm period: a tiny swap barely moves the price
+            ink::env::test::advance_block::<Environment>();
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            let calmFee = dex.getSwapFeeBps();
+            assert_eq!(calmFee, 10);
+
+            // A large price moving swap raises the fee up to its cap, as seen by a caller without volume discounts
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            dex.faucet(5_000_000, 0).unwrap();
+            assert!(dex.swapToken1GivenToken1(5_000_000, 0, u64::MAX).is_ok());
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(dex.getSwapFeeBps(), 100);
+            assert!(dex.getSwapFeeBps() > calmFee);
+        }
+
+        #[ink::test]
+        fn combined_position_requires_token() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let dex = Dex::new(3);
+            assert_eq!(
+                dex.getCombinedPosition(accounts.alice),
+                Err(Error::TokenNotSet)
+            );
+        }
+
+        #[ink::test]
+        fn protocol_revenue_in_token1_works() {
+            let mut dex = Dex::new(10);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.getProtocolRevenueInToken1(), Err(Error::ZeroLiquidity));
+            assert_eq!(dex.setProtocolFeeBps(5_000), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            assert!(dex.provide(100_000, 200_000, u64::MAX).is_ok());
+
+            // 1% fee on 10_000 of each token, half of which goes to the protocol
+            assert!(dex.swapToken1GivenToken1(10_000, 0, u64::MAX).is_ok());
+            assert!(dex.swapToken2GivenToken2(10_000, 0, u64::MAX).is_ok());
+            assert_eq!(dex.getProtocolFees(), (50, 50));
+
+            let (totalToken1, totalToken2, _, _) = dex.getPoolDetails();
+            assert_eq!(
+                dex.getProtocolRevenueInToken1(),
+                Ok(50 + 50 * totalToken1 / totalToken2)
+            );
+
+            // Fees of an 18 decimal pool whose product with the reserves exceeds u128
+            let mut dex = Dex::new(10);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10u128.pow(25), 10u128.pow(25)).unwrap();
+            assert!(dex
+                .provide(10u128.pow(24), 2 * 10u128.pow(24), u64::MAX)
+                .is_ok());
+            dex.protocolFeesToken2 = 10u128.pow(21);
+            assert_eq!(dex.getProtocolRevenueInToken1(), Ok(10u128.pow(21) / 2));
+        }
+
+        #[ink::test]
+        fn swap_to_denied_recipient_fails() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.provide(10_000, 10_000, u64::MAX).is_ok());
+            assert_eq!(dex.setRecipientDenied(accounts.eve, true), Ok(()));
+
+            assert_eq!(
+                dex.swapTo(true, 1_000, 0, accounts.eve, u64::MAX),
+                Err(Error::ComplianceRejected)
+            );
+
+            let amountOut = dex
+                .swapTo(true, 1_000, 0, accounts.bob, u64::MAX)
+                .unwrap()
+                .amountOut;
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.getMyHoldings(), (0, amountOut, 0));
+        }
+
+        #[ink::test]
+        fn imbalance_trend_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.getImbalanceTrend(), Err(Error::InsufficientHistory));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            assert!(dex.provide(100_000, 100_000, u64::MAX).is_ok());
+            assert_eq!(dex.getImbalanceTrend(), Err(Error::InsufficientHistory));
+
+            // Repeatedly selling Token1 keeps growing the imbalance
+            for _ in 0..3 {
+                ink::env::test::advance_block::<Environment>();
+                assert!(dex.swapToken1GivenToken1(5_000, 0, u64::MAX).is_ok());
+                assert!(dex.getImbalanceTrend().unwrap() > 0);
+            }
+
+            // Selling Token2 back reverses it
+            ink::env::test::advance_block::<Environment>();
+            assert!(dex.swapToken2GivenToken2(20_000, 0, u64::MAX).is_ok());
+            assert!(dex.getImbalanceTrend().unwrap() < 0);
+
+            // Withdraws checkpoint the reserves left in the pool
+            ink::env::test::advance_block::<Environment>();
+            let share = dex.getMyHoldings().2;
+            assert!(dex.withdraw(share / 2, u64::MAX).is_ok());
+            let (_, token1, token2) = *dex.getCheckpoints().last().unwrap();
+            assert_eq!((token1, token2), (dex.totalToken1, dex.totalToken2));
+        }
+
+        #[ink::test]
+        fn default_slippage_swap_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            assert!(dex.provide(100_000, 100_000, u64::MAX).is_ok());
+            assert_eq!(dex.setDefaultSlippage(100), Ok(()));
+
+            // Stable price: the default slippage swap succeeds
+            let quote = dex.getSwapToken1EstimateGivenToken1(1_000).unwrap();
+            assert!(dex
+                .swapToken1WithDefaultSlippage(1_000, quote, u64::MAX)
+                .is_ok());
+
+            // A large reserve shift after quoting pushes the output below the default tolerance
+            let quote = dex.getSwapToken1EstimateGivenToken1(1_000).unwrap();
+            assert!(dex.swapToken1GivenToken1(50_000, 0, u64::MAX).is_ok());
+            assert_eq!(
+                dex.swapToken1WithDefaultSlippage(1_000, quote, u64::MAX),
+                Err(Error::SlippageExceeded)
+            );
+        }
+
+        #[ink::test]
+        fn swap_curve_is_increasing_and_concave() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(
+                dex.sampleSwapCurve(true, 5, 1_000),
+                Err(Error::ZeroLiquidity)
+            );
+            dex.faucet(2_000_000_000, 2_000_000_000).unwrap();
+            assert!(dex.provide(1_000_000_000, 1_000_000_000, u64::MAX).is_ok());
+
+            let curve = dex.sampleSwapCurve(true, 5, 400_000_000).unwrap();
+            assert_eq!(curve.len(), 5);
+            assert_eq!(curve[0], (0, 0));
+            assert_eq!(curve[4].0, 400_000_000);
+
+            let increments: Vec<Balance> = curve.windows(2).map(|p| p[1].1 - p[0].1).collect();
+            assert!(increments.iter().all(|increment| *increment > 0));
+            assert!(increments.windows(2).all(|i| i[1] < i[0]));
+
+            assert_eq!(dex.sampleSwapCurve(false, 200, 1_000).unwrap().len(), 64);
+        }
+
+        #[ink::test]
+        fn admin_action_cooldown_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            ink::env::test::set_block_timestamp::<Environment>(1_000);
+            assert_eq!(dex.setAdminActionCooldown(500), Ok(()));
+
+            ink::env::test::set_block_timestamp::<Environment>(1_200);
+            assert_eq!(
+                dex.setProtocolFeeBps(1_000),
+                Err(Error::AdminCooldownActive)
+            );
+            assert_eq!(
+                dex.setDynamicFee(true, 10, 100),
+                Err(Error::AdminCooldownActive)
+            );
+
+            assert_eq!(
+                dex.setActiveProviderRebate(1_000),
+                Err(Error::AdminCooldownActive)
+            );
+            assert_eq!(
+                dex.setTreasury(AccountId::from([0x02; 32])),
+                Err(Error::AdminCooldownActive)
+            );
+            assert_eq!(dex.pause(), Err(Error::AdminCooldownActive));
+
+            // User operations are unaffected
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+
+            ink::env::test::set_block_timestamp::<Environment>(1_500);
+            assert_eq!(dex.setProtocolFeeBps(1_000), Ok(()));
+            assert_eq!(dex.getProtocolFeeBps(), 1_000);
+
+            ink::env::test::set_block_timestamp::<Environment>(2_000);
+            assert_eq!(dex.pause(), Ok(()));
+            assert_eq!(dex.unpause(), Err(Error::AdminCooldownActive));
+            ink::env::test::set_block_timestamp::<Environment>(2_500);
+            assert_eq!(dex.unpause(), Ok(()));
+        }
+
+        #[ink::test]
+        fn rebalance_hint_points_to_peg() {
+            let mut dex = Dex::new(0);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.getRebalanceHint(), Err(Error::ZeroLiquidity));
+            dex.faucet(10_000_000, 10_000_000).unwrap();
+            assert!(dex.provide(1_000_000, 1_000_000, u64::MAX).is_ok());
+            assert_eq!(dex.getRebalanceHint(), Ok((false, 0)));
+
+            // Selling Token1 pushes the ratio below the 1:1 peg, so the hint is to sell Token2
+            assert!(dex.swapToken1GivenToken1(100_000, 0, u64::MAX).is_ok());
+            let (token1In, amount) = dex.getRebalanceHint().unwrap();
+            assert!(!token1In);
+            assert!(amount > 0);
+
+            assert!(dex.swapToken2GivenToken2(amount, 0, u64::MAX).is_ok());
+            let (totalToken1, totalToken2, _, _) = dex.getPoolDetails();
+            assert!(totalToken1.abs_diff(totalToken2) < 10);
+
+            // 18 decimal reserves whose constant product exceeds u128, the 1:1 peg holds 2e24 of each
+            let mut dex = Dex::new(0);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10u128.pow(25), 10u128.pow(25)).unwrap();
+            assert!(dex
+                .provide(10u128.pow(24), 4 * 10u128.pow(24), u64::MAX)
+                .is_ok());
+            assert_eq!(dex.getRebalanceHint(), Ok((true, 10u128.pow(24))));
+
+            let mut weighted = Dex::newWeighted(0, 80);
+            assert_eq!(weighted.setFaucetEnabled(true), Ok(()));
+            weighted.faucet(10_000, 10_000).unwrap();
+            assert!(weighted.provide(1_000, 1_000, u64::MAX).is_ok());
+            assert_eq!(weighted.getRebalanceHint(), Err(Error::UnsupportedPool));
+        }
+
+        #[ink::test]
+        fn batch_swap_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            assert!(dex.provide(100_000, 100_000, u64::MAX).is_ok());
+
+            let first = dex.getSwapToken1EstimateGivenToken1(1_000).unwrap();
+            let amounts = dex
+                .batchSwap(
+                    vec![
+                        SwapOrder {
+                            token1In: true,
+                            amountIn: 1_000,
+                            minOut: first,
+                        },
+                        SwapOrder {
+                            token1In: true,
+                            amountIn: 1_000,
+                            minOut: 1,
+                        },
+                    ],
+                    u64::MAX,
+                )
+                .unwrap();
+            assert_eq!(amounts.len(), 2);
+            assert_eq!(amounts[0].amountOut, first);
+
+            // The second leg executes against the reserves left by the first one
+            assert!(amounts[1].amountOut < amounts[0].amountOut);
+        }
+
+        #[ink::test]
+        fn batch_swap_reverts_on_failing_leg() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            assert!(dex.provide(100_000, 100_000, u64::MAX).is_ok());
+
+            // On chain the error reverts the first leg along with the failing one
+            assert_eq!(
+                dex.batchSwap(
+                    vec![
+                        SwapOrder {
+                            token1In: true,
+                            amountIn: 1_000,
+                            minOut: 1
+                        },
+                        SwapOrder {
+                            token1In: false,
+                            amountIn: 1_000,
+                            minOut: 2_000
+                        },
+                    ],
+                    u64::MAX
+                ),
+                Err(Error::SlippageExceeded)
+            );
+        }
+
+        #[ink::test]
+        fn fee_tier_reflects_volume() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(10);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(100_000_000, 100_000_000).unwrap();
+            assert!(dex.provide(10_000_000, 10_000_000, u64::MAX).is_ok());
+            assert_eq!(dex.getUserFeeTier(accounts.alice), (0, 100));
+
+            for _ in 0..4 {
+                assert!(dex.swapToken1GivenToken1(300_000, 0, u64::MAX).is_ok());
+                assert!(dex.swapToken2GivenToken2(300_000, 0, u64::MAX).is_ok());
+            }
+
+            // 2_400_000 of volume reaches the second discount tier
+            assert_eq!(dex.getUserFeeTier(accounts.alice), (2, 75));
+            assert_eq!(dex.getUserFeeTier(accounts.bob), (0, 100));
+            assert_eq!(dex.getSwapFeeBps(), 75);
+        }
+
+        #[ink::test]
+        fn concentration_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.getConcentration(vec![accounts.alice]), 0);
+
+            dex.faucet(700, 700).unwrap();
+            assert!(dex.provide(700, 700, u64::MAX).is_ok());
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            dex.faucet(300, 300).unwrap();
+            assert!(dex.provide(300, 300, u64::MAX).is_ok());
+
+            assert_eq!(
+                dex.getConcentration(vec![accounts.alice, accounts.bob]),
+                6_999
+            );
+            assert_eq!(dex.getConcentration(vec![accounts.bob]), 2_999);
+        }
+
+        #[ink::test]
+        fn default_price_follows_quote_token() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert!(dex.getQuoteToken());
+            assert_eq!(dex.getPrice(None), Err(Error::ZeroLiquidity));
+            dex.faucet(1_000, 2_000).unwrap();
+            assert!(dex.provide(1_000, 2_000, u64::MAX).is_ok());
+            assert_eq!(dex.getPrice(None), Ok(2 * crate::PRECISION));
+            assert_eq!(dex.getPrice(Some(false)), Ok(crate::PRECISION / 2));
+
+            let mut dex = Dex::newWithQuote(3, false);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert!(!dex.getQuoteToken());
+            dex.faucet(1_000, 2_000).unwrap();
+            assert!(dex.provide(1_000, 2_000, u64::MAX).is_ok());
+            assert_eq!(dex.getPrice(None), Ok(crate::PRECISION / 2));
+            assert_eq!(dex.getPrice(Some(true)), Ok(2 * crate::PRECISION));
+        }
+
+        #[ink::test]
+        fn provider_value_history_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(10);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.setValueHistoryOptIn(true);
+            dex.faucet(1_000, 1_000).unwrap();
+            let share = dex.provide(1_000, 1_000, u64::MAX).unwrap();
+
+            // Round trip swaps accrue fees to the pool
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            dex.faucet(1_000, 0).unwrap();
+            let amountToken2 = dex
+                .swapToken1GivenToken1(1_000, 0, u64::MAX)
+                .unwrap()
+                .amountOut;
+            assert!(dex.swapToken2GivenToken2(amountToken2, 0, u64::MAX).is_ok());
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            ink::env::test::advance_block::<Environment>();
+            assert!(dex.withdraw(share / 2, u64::MAX).is_ok());
+
+            let history = dex.getProviderValueHistory(accounts.alice);
+            assert_eq!(history.len(), 2);
+            assert_eq!(history[0].1, 1_998);
+            assert!(history[1].0 > history[0].0);
+            // The remaining half of the position is worth more than half of the initial value
+            assert!(history[1].1 * 2 > history[0].1);
+            assert!(dex.getProviderValueHistory(accounts.bob).is_empty());
+        }
+
+        #[ink::test]
+        fn provider_value_history_large_reserves() {
+            // Positions of an 18 decimal pool whose value products exceed u128
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(10);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.setValueHistoryOptIn(true);
+            dex.faucet(10u128.pow(25), 10u128.pow(25)).unwrap();
+            assert!(dex
+                .provide(10u128.pow(24), 10u128.pow(24), u64::MAX)
+                .is_ok());
+            let share = dex
+                .provide(10u128.pow(24), 10u128.pow(24), u64::MAX)
+                .unwrap();
+            assert!(dex.withdraw(share, u64::MAX).is_ok());
+            let history = dex.getProviderValueHistory(accounts.alice);
+            assert_eq!(history.len(), 3);
+            // All of the 4e24 pool but the locked minimum liquidity
+            assert!(history[1].1 < 4 * 10u128.pow(24));
+            assert!(history[1].1 > 4 * 10u128.pow(24) / 1_000_000 * 999_990);
+        }
+
+        #[ink::test]
+        fn swap_out_cooldown_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.provide(10_000, 10_000, u64::MAX).is_ok());
+            assert_eq!(dex.setSwapOutCooldown(2), Ok(()));
+            assert_eq!(dex.getSwapOutCooldown(), 2);
+
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            assert_eq!(
+                dex.swapToken2GivenToken2(1_000, 0, u64::MAX),
+                Err(Error::CooldownActive)
+            );
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(
+                dex.swapToken1GivenToken1(1_000, 0, u64::MAX),
+                Err(Error::CooldownActive)
+            );
+
+            ink::env::test::advance_block::<Environment>();
+            assert!(dex.swapToken2GivenToken2(1_000, 0, u64::MAX).is_ok());
+
+            // A batch is held to the cooldown once, not once per leg
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::advance_block::<Environment>();
+            let order = SwapOrder {
+                token1In: true,
+                amountIn: 1_000,
+                minOut: 0,
+            };
+            let results = dex
+                .batchSwap(vec![order.clone(), order.clone()], u64::MAX)
+                .unwrap();
+            assert_eq!(results.len(), 2);
+            assert_eq!(
+                dex.batchSwap(vec![order], u64::MAX),
+                Err(Error::CooldownActive)
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.setSwapOutCooldown(0), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn get_position_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(
+                dex.getPosition(accounts.alice),
+                Position {
+                    token1: 0,
+                    token2: 0,
+                    shares: 0,
+                    ownershipBps: 0,
+                    withdrawableToken1: 0,
+                    withdrawableToken2: 0,
+                }
+            );
+
+            dex.faucet(10_000, 10_000).unwrap();
+            let share = dex.provide(1_000, 2_000, u64::MAX).unwrap();
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            dex.faucet(10_000, 10_000).unwrap();
+            let bobShare = dex.provide(1_000, 2_000, u64::MAX).unwrap();
+
+            // Queried for another account than the caller
+            let (_, _, totalShares, _) = dex.getPoolDetails();
+            let (withdrawableToken1, withdrawableToken2) = dex.getWithdrawEstimate(share).unwrap();
+            assert_eq!(
+                dex.getPosition(accounts.alice),
+                Position {
+                    token1: 9_000,
+                    token2: 8_000,
+                    shares: share,
+                    ownershipBps: share * crate::BPS / totalShares,
+                    withdrawableToken1,
+                    withdrawableToken2,
+                }
+            );
+            assert!(dex.getPosition(accounts.alice).ownershipBps < 5_000);
+            assert_eq!(dex.getPosition(accounts.bob).shares, bobShare);
+            assert_eq!(dex.getPosition(accounts.bob).ownershipBps, 5_000);
+        }
+
+        #[ink::test]
+        fn stats_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert!(!dex.stats().isActive);
+
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            assert!(dex.swapToken2GivenToken2(500, 0, u64::MAX).is_ok());
+
+            let (totalToken1, totalToken2, totalShares, _) = dex.getPoolDetails();
+            assert_eq!(
+                dex.stats(),
+                PoolStats {
+                    totalToken1,
+                    totalToken2,
+                    totalShares,
+                    tradeCount: 2,
+                    isActive: true,
+                    paused: false,
+                }
+            );
+        }
+
+        #[ink::test]
+        fn active_provider_rebate_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(10);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.setProtocolFeeBps(5_000), Ok(()));
+            assert_eq!(dex.setActiveProviderRebate(crate::BPS), Ok(()));
+            assert_eq!(dex.getActiveProviderRebate(), crate::BPS);
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            dex.faucet(100, 100).unwrap();
+            assert!(dex.provide(100, 100, u64::MAX).is_ok());
+
+            // 1% fee on 1_000, half of it kept by the protocol & fully rebated to bob
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            dex.faucet(1_000, 0).unwrap();
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.getMyHoldings().0, 5);
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(dex.getMyHoldings().0, 9_000);
+            assert_eq!(dex.getProtocolFees(), (0, 0));
+        }
+
+        #[ink::test]
+        fn emergency_withdraw_timelock_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 2_000, u64::MAX).is_ok());
+            assert_eq!(dex.executeEmergency(), Err(Error::EmergencyNotRequested));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.requestEmergency(), Err(Error::NotOwner));
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+
+            let events = ink::env::test::recorded_events().count();
+            let executableAt = dex.requestEmergency().unwrap();
+            assert_eq!(executableAt, crate::DEFAULT_EMERGENCY_TIMELOCK);
+            assert_eq!(ink::env::test::recorded_events().count(), events + 1);
+            assert_eq!(dex.setEmergencyTimelock(0), Err(Error::TimelockActive));
+
+            ink::env::test::set_block_timestamp::<Environment>(executableAt - 1);
+            assert_eq!(dex.executeEmergency(), Err(Error::TimelockActive));
+
+            ink::env::test::set_block_timestamp::<Environment>(executableAt);
+            assert_eq!(dex.executeEmergency(), Ok((1_000, 2_000)));
+            assert_eq!(dex.getMyHoldings().0, 10_000);
+            assert!(dex.stats().paused);
+            assert_eq!(
+                dex.getEmergencyTimelock(),
+                (crate::DEFAULT_EMERGENCY_TIMELOCK, None)
+            );
+        }
+
+        #[ink::test]
+        fn min_remaining_shares_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000, 1_000).unwrap();
+            let share = dex.provide(1_000, 1_000, u64::MAX).unwrap();
+            assert_eq!(dex.setMinRemainingShares(share / 10), Ok(()));
+            assert_eq!(dex.getMinRemainingShares(), share / 10);
+
+            assert_eq!(
+                dex.withdraw(share - share / 20, u64::MAX),
+                Err(Error::DustPositionNotAllowed)
+            );
+            assert!(dex.withdraw(share / 2, u64::MAX).is_ok());
+            assert!(dex.withdraw(share / 2, u64::MAX).is_ok());
+            // The reserves backing MINIMUM_LIQUIDITY stay in the pool
+            assert_eq!(dex.getMyHoldings(), (999, 999, 0));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.setMinRemainingShares(0), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn tvl_breakdown_works() {
+            let mut dex = Dex::new(10);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+            assert_eq!(dex.getTVLBreakdown(), (1_000, 1_000, 0, 0));
+
+            let amountToken2 = dex
+                .swapToken1GivenToken1(1_000, 0, u64::MAX)
+                .unwrap()
+                .amountOut;
+            assert!(dex.swapToken2GivenToken2(amountToken2, 0, u64::MAX).is_ok());
+            let (principal1, principal2, fees1, fees2) = dex.getTVLBreakdown();
+            assert_eq!((principal1, principal2), (1_000, 1_000));
+            assert!(fees1 + fees2 > 0);
+
+            let amountToken2 = dex
+                .swapToken1GivenToken1(1_000, 0, u64::MAX)
+                .unwrap()
+                .amountOut;
+            assert!(dex.swapToken2GivenToken2(amountToken2, 0, u64::MAX).is_ok());
+            let (principal1, principal2, moreFees1, moreFees2) = dex.getTVLBreakdown();
+            assert_eq!((principal1, principal2), (1_000, 1_000));
+            assert!(moreFees1 + moreFees2 > fees1 + fees2);
+        }
+
+        #[ink::test]
+        fn matching_amount_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.getMatchingAmount(true, 100), Err(Error::ZeroLiquidity));
+            dex.faucet(1_000, 3_000).unwrap();
+            assert!(dex.provide(1_000, 3_000, u64::MAX).is_ok());
+
+            assert_eq!(
+                dex.getMatchingAmount(true, 100),
+                dex.getEquivalentToken2Estimate(100)
+            );
+            assert_eq!(dex.getMatchingAmount(true, 100), Ok(300));
+            assert_eq!(dex.getMatchingAmount(false, 300), Ok(100));
+        }
+
+        #[ink::test]
+        fn buyback_and_retire_checks() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(10);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+            assert_eq!(dex.buybackAndRetire(0), Err(Error::ZeroAmount));
+
+            assert_eq!(dex.setProtocolFeeBps(crate::BPS), Ok(()));
+            assert!(dex.swapToken2GivenToken2(1_000, 0, u64::MAX).is_ok());
+            assert_eq!(dex.getProtocolFees(), (0, 10));
+
+            // The retired CarbonTokens must be the ones backing the Token1 reserve
+            assert_eq!(dex.buybackAndRetire(0), Err(Error::TokenNotSet));
+            assert_eq!(dex.setCarbonToken(accounts.django), Ok(()));
+            assert_eq!(dex.buybackAndRetire(0), Err(Error::UnsupportedPool));
+            dex.token1 = Some(accounts.django);
+            assert_eq!(dex.buybackAndRetire(1_000), Err(Error::SlippageExceeded));
+
+            // Only the treasury may buy back, even if it is not the owner
+            assert_eq!(dex.setTreasury(accounts.bob), Ok(()));
+            assert_eq!(dex.buybackAndRetire(0), Err(Error::NotTreasury));
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.buybackAndRetire(1_000), Err(Error::SlippageExceeded));
+        }
+
+        #[ink::test]
+        fn swap_token1_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert_eq!(dex.swapToken1(100, 0, u64::MAX), Err(Error::ZeroLiquidity));
+            assert!(dex.provide(1_000, 2_000, u64::MAX).is_ok());
+
+            assert_eq!(dex.swapToken1(0, 0, u64::MAX), Err(Error::ZeroAmount));
+            assert_eq!(
+                dex.swapToken1(100_000, 0, u64::MAX),
+                Err(Error::InsufficientAmount)
+            );
+            let estimate = dex.getSwapToken1EstimateGivenToken1(1_000).unwrap();
+            assert_eq!(
+                dex.swapToken1(1_000, estimate + 1, u64::MAX),
+                Err(Error::SlippageExceeded)
+            );
+
+            assert_eq!(
+                dex.swapToken1(1_000, estimate, u64::MAX)
+                    .map(|result| result.amountOut),
+                Ok(estimate)
+            );
+            assert_eq!(
+                dex.getMyHoldings(),
+                (
+                    8_000,
+                    8_000 + estimate,
+                    100 * crate::PRECISION - crate::MINIMUM_LIQUIDITY
+                )
+            );
+            let (totalToken1, totalToken2, _, _) = dex.getPoolDetails();
+            assert_eq!((totalToken1, totalToken2), (2_000, 2_000 - estimate));
+        }
+
+        #[ink::test]
+        fn swap_token2_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(2_000, 1_000, u64::MAX).is_ok());
+
+            assert_eq!(dex.swapToken2(0, 0, u64::MAX), Err(Error::ZeroAmount));
+            assert_eq!(
+                dex.swapToken2(100_000, 0, u64::MAX),
+                Err(Error::InsufficientAmount)
+            );
+            let estimate = dex.getSwapToken2EstimateGivenToken2(1_000).unwrap();
+            assert_eq!(
+                dex.swapToken2(1_000, estimate + 1, u64::MAX),
+                Err(Error::SlippageExceeded)
+            );
+
+            assert_eq!(
+                dex.swapToken2(1_000, estimate, u64::MAX)
+                    .map(|result| result.amountOut),
+                Ok(estimate)
+            );
+            assert_eq!(
+                dex.getMyHoldings(),
+                (
+                    8_000 + estimate,
+                    8_000,
+                    100 * crate::PRECISION - crate::MINIMUM_LIQUIDITY
+                )
+            );
+            let (totalToken1, totalToken2, _, _) = dex.getPoolDetails();
+            assert_eq!((totalToken1, totalToken2), (2_000 - estimate, 2_000));
+        }
+
+        #[ink::test]
+        fn swap_estimates_match_swaps() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.getSwapToken1Estimate(100), Err(Error::ZeroLiquidity));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 2_000, u64::MAX).is_ok());
+
+            let estimate = dex.getSwapToken1Estimate(1_000).unwrap();
+            assert_eq!(
+                dex.swapToken1(1_000, 0, u64::MAX)
+                    .map(|result| result.amountOut),
+                Ok(estimate)
+            );
+            let estimate = dex.getSwapToken2Estimate(1_000).unwrap();
+            assert_eq!(
+                dex.swapToken2(1_000, 0, u64::MAX)
+                    .map(|result| result.amountOut),
+                Ok(estimate)
+            );
+
+            let required = dex.getSwapToken1EstimateGivenToken2(500).unwrap();
+            assert_eq!(
+                dex.swapToken1GivenToken2(500, required, u64::MAX)
+                    .map(|result| result.amountIn),
+                Ok(required)
+            );
+            assert_eq!(
+                dex.getSwapToken1EstimateGivenToken2(10_000),
+                Err(Error::InsufficientLiquidity)
+            );
+        }
+
+        #[ink::test]
+        fn state_transitions_emit_events() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), 0);
+
+            let share = dex.provide(1_000, 1_000, u64::MAX).unwrap();
+            assert_eq!(ink::env::test::recorded_events().count(), 1);
+            assert!(dex.swapToken1(1_000, 0, u64::MAX).is_ok());
+            assert!(dex.swapToken2(1_000, 0, u64::MAX).is_ok());
+            assert!(dex.swapToken1GivenToken2(500, 1_000, u64::MAX).is_ok());
+            assert!(dex.swapToken2GivenToken1(500, 1_000, u64::MAX).is_ok());
+            assert_eq!(ink::env::test::recorded_events().count(), 5);
+            assert!(dex.withdraw(share, u64::MAX).is_ok());
+            assert_eq!(ink::env::test::recorded_events().count(), 6);
+
+            // Failed calls emit nothing
+            assert!(dex.swapToken1(0, 0, u64::MAX).is_err());
+            assert_eq!(ink::env::test::recorded_events().count(), 6);
+        }
+
+        #[ink::test]
+        fn new_with_tokens_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let dex = Dex::newWithTokens(3, accounts.django, accounts.frank);
+            assert_eq!(
+                dex.getTokens(),
+                (Some(accounts.django), Some(accounts.frank))
+            );
+            assert_eq!(Dex::new(3).getTokens(), (None, None));
+        }
+
+        #[ink::test]
+        fn fee_accrual_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(100);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.provide(10_000, 10_000, u64::MAX).is_ok());
+            assert_eq!(dex.getClaimableFees(), (0, 0));
+            let share = dex.getMyHoldings().2;
+            let (before1, before2) = dex.getWithdrawEstimate(share).unwrap();
+
+            // Round trips leave the reserves balanced, so only the fees grow the position
+            for _ in 0..5 {
+                let amountToken2 = dex
+                    .swapToken1GivenToken1(1_000, 0, u64::MAX)
+                    .unwrap()
+                    .amountOut;
+                assert!(dex.swapToken2GivenToken2(amountToken2, 0, u64::MAX).is_ok());
+            }
+            let (fees1, fees2) = dex.getClaimableFees();
+            assert!(fees1 > 0 && fees2 > 0);
+            let (after1, after2) = dex.getWithdrawEstimate(share).unwrap();
+            assert!(after1 + after2 > before1 + before2);
+
+            // Fees are only earned by providers holding shares
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.getClaimableFees(), (0, 0));
+
+            // Withdrawing half of the position pays out half of the accrued fees
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert!(dex.withdraw(share / 2, u64::MAX).is_ok());
+            assert_eq!(
+                dex.getClaimableFees(),
+                (fees1 - fees1 / 2, fees2 - fees2 / 2)
+            );
+        }
+
+        #[ink::test]
+        fn fee_accrual_works_with_large_fees() {
+            // An 18 decimal pool whose swap fee times FEE_GROWTH_PRECISION exceeds u128
+            let mut dex = Dex::new(100);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10u128.pow(32), 10u128.pow(32)).unwrap();
+            assert!(dex
+                .provide(10u128.pow(31), 10u128.pow(31), u64::MAX)
+                .is_ok());
+
+            assert!(dex
+                .swapToken1GivenToken1(10u128.pow(30), 0, u64::MAX)
+                .is_ok());
+            let (fees1, fees2) = dex.getClaimableFees();
+            assert!(fees1 > 10u128.pow(26) && fees1 < 10u128.pow(30));
+            assert_eq!(fees2, 0);
+        }
+
+        #[ink::test]
+        fn collect_protocol_fees_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::newWithTreasury(100, accounts.charlie, 5_000);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.getTreasury(), accounts.charlie);
+            assert_eq!(dex.getProtocolFeeBps(), 5_000);
+            assert_eq!(
+                Dex::newWithTreasury(100, accounts.charlie, 10_001).getProtocolFeeBps(),
+                0
+            );
+
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.provide(10_000, 10_000, u64::MAX).is_ok());
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            assert!(dex.swapToken2GivenToken2(1_000, 0, u64::MAX).is_ok());
+            let fees = dex.getProtocolFees();
+            assert_eq!(fees, (50, 50));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.collectProtocolFees(), Err(Error::NotOwner));
+            assert_eq!(dex.setTreasury(accounts.bob), Err(Error::NotOwner));
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let events = ink::env::test::recorded_events().count();
+            assert_eq!(dex.collectProtocolFees(), Ok(fees));
+            assert_eq!(ink::env::test::recorded_events().count(), events + 1);
+            assert_eq!(dex.getProtocolFees(), (0, 0));
+
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(dex.getMyHoldings(), (50, 50, 0));
+        }
+
+        #[ink::test]
+        fn large_reserves_do_not_overflow() {
+            let reserve = u128::MAX / 2;
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(reserve + 1_000_000, reserve + 1_000_000)
+                .unwrap();
+            assert!(dex.provide(reserve, reserve, u64::MAX).is_ok());
+
+            // The product of the reserves does not fit in a u128
+            let amountToken2 = dex.getSwapToken1EstimateGivenToken1(1_000_000).unwrap();
+            assert!(amountToken2 > 0 && amountToken2 < 1_000_000);
+            assert_eq!(
+                dex.swapToken1GivenToken1(1_000_000, 0, u64::MAX)
+                    .map(|result| result.amountOut),
+                Ok(amountToken2)
+            );
+            assert!(dex
+                .swapToken2GivenToken1(1_000, Balance::MAX, u64::MAX)
+                .is_ok());
+
+            // Draining almost all Token2 requires more Token1 than a Balance can hold
+            let (totalToken1, totalToken2, _, _) = dex.getPoolDetails();
+            assert!(totalToken1 > reserve);
+            assert_eq!(
+                dex.getSwapToken1EstimateGivenToken2(totalToken2 - 1),
+                Err(Error::InsufficientLiquidity)
+            );
+        }
+
+        #[ink::test]
+        fn mul_div_works() {
+            assert_eq!(crate::mulDiv(6, 7, 4), Some(10));
+            assert_eq!(
+                crate::mulDiv(u128::MAX, u128::MAX, u128::MAX),
+                Some(u128::MAX)
+            );
+            assert_eq!(crate::mulDiv(1 << 100, 1 << 100, 1 << 90), Some(1 << 110));
+            assert_eq!(crate::mulDiv(u128::MAX, 2, 1), None);
+            assert_eq!(crate::mulDiv(1, 1, 0), None);
+        }
+
+        #[ink::test]
+        fn spot_price_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.getSpotPriceToken1(), Err(Error::ZeroLiquidity));
+            assert_eq!(dex.getSpotPriceToken2(), Err(Error::ZeroLiquidity));
+
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 4_000, u64::MAX).is_ok());
+            assert_eq!(dex.getSpotPriceToken1(), Ok(4 * crate::PRECISION));
+            assert_eq!(dex.getSpotPriceToken2(), Ok(crate::PRECISION / 4));
+
+            // Spot excludes fees & slippage, so a swap receives less than it suggests
+            let amountToken2 = dex
+                .swapToken1GivenToken1(1_000, 0, u64::MAX)
+                .unwrap()
+                .amountOut;
+            assert!(amountToken2 < 1_000 * 4);
+
+            // Reserves whose PRECISION scaled weighted value exceeds u128
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10u128.pow(32), 10u128.pow(32)).unwrap();
+            assert!(dex
+                .provide(10u128.pow(31), 4 * 10u128.pow(31), u64::MAX)
+                .is_ok());
+            assert_eq!(dex.getSpotPriceToken1(), Ok(4 * crate::PRECISION));
+            assert_eq!(dex.getSpotPriceToken2(), Ok(crate::PRECISION / 4));
+        }
+
+        #[ink::test]
+        fn minimum_liquidity_prevents_share_inflation() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+
+            // The attacker seeds the pool with dust & withdraws every share it can
+            let share = dex.provide(1, 1, u64::MAX).unwrap();
+            assert_eq!(share, 100 * crate::PRECISION - crate::MINIMUM_LIQUIDITY);
+            assert!(dex.withdraw(share, u64::MAX).is_ok());
+            assert_eq!(dex.getPoolDetails(), (1, 1, crate::MINIMUM_LIQUIDITY, 3));
+
+            // Then inflates the Token1 reserve without minting shares. Swaps with no output are rejected,
+            // so the donation is simulated
+            assert_eq!(
+                dex.swapToken1GivenToken1(999_999, 0, u64::MAX),
+                Err(Error::AmountTooSmall)
+            );
+            dex.totalToken1 += 999_999;
+            assert_eq!(dex.getPoolDetails().0, 1_000_000);
+
+            // The next provider still receives shares worth its whole deposit
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            dex.faucet(1_000_000, 1).unwrap();
+            assert_eq!(
+                dex.provide(1_000_000, 1, u64::MAX),
+                Ok(crate::MINIMUM_LIQUIDITY)
+            );
+            assert_eq!(
+                dex.getWithdrawEstimate(crate::MINIMUM_LIQUIDITY),
+                Ok((1_000_000, 1))
+            );
+        }
+
+        #[ink::test]
+        fn balance_updates_read_modify_write() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000, 2_000).unwrap();
+            assert_eq!(dex.getMyHoldings(), (1_000, 2_000, 0));
+            dex.faucet(500, 0).unwrap();
+            assert_eq!(dex.getMyHoldings(), (1_500, 2_000, 0));
+
+            // First provide inserts the position, the next one adds to it
+            let first = dex.provide(100, 200, u64::MAX).unwrap();
+            assert_eq!(dex.getMyHoldings(), (1_400, 1_800, first));
+            assert_eq!(dex.getProviderLimit().0, 1);
+            let second = dex.provide(50, 100, u64::MAX).unwrap();
+            assert_eq!(dex.getMyHoldings(), (1_350, 1_700, first + second));
+            assert_eq!(dex.getProviderLimit().0, 1);
+
+            assert!(dex.withdraw(second, u64::MAX).is_ok());
+            assert_eq!(dex.getMyHoldings().2, first);
+            assert!(dex.withdraw(first, u64::MAX).is_ok());
+            assert_eq!(dex.getMyHoldings().2, 0);
+            assert_eq!(dex.getProviderLimit().0, 0);
+        }
+
+        #[ink::test]
+        fn cumulative_prices_work() {
+            let mut dex = Dex::new(0);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            ink::env::test::set_block_timestamp::<Environment>(100);
+            assert!(dex.provide(1_000, 2_000, u64::MAX).is_ok());
+            // An empty pool has no price to accumulate
+            assert_eq!(dex.getCumulativePrices(), (0, 0, 100));
+
+            ink::env::test::set_block_timestamp::<Environment>(110);
+            assert!(dex.provide(1_000, 2_000, u64::MAX).is_ok());
+            let (price1, price2, timestamp) = dex.getCumulativePrices();
+            assert_eq!(price1, 2 * crate::PRECISION * 10);
+            assert_eq!(price2, crate::PRECISION / 2 * 10);
+            assert_eq!(timestamp, 110);
+
+            // A swap accumulates the price held before it, then moves the spot price
+            ink::env::test::set_block_timestamp::<Environment>(130);
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            let (price1, _, _) = dex.getCumulativePrices();
+            assert_eq!(price1, 2 * crate::PRECISION * 30);
+            let spotPrice1 = dex.getSpotPriceToken1().unwrap();
+
+            // The time-weighted average over the next interval reflects the post-swap price
+            ink::env::test::set_block_timestamp::<Environment>(150);
+            assert!(dex.withdraw(crate::PRECISION, u64::MAX).is_ok());
+            let (newPrice1, _, _) = dex.getCumulativePrices();
+            assert_eq!(newPrice1.wrapping_sub(price1) / 20, spotPrice1);
+        }
+
+        #[ink::test]
+        fn provide_with_min_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(0);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.provide(10_000, 10_000, u64::MAX).is_ok());
+            let minShares = dex.getMinSharesForAmounts(1_000, 1_000).unwrap();
+
+            // A swap lands between the estimate & the provide, shifting the pool ratio
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.swapToken1GivenToken1(2_500, 0, u64::MAX).is_ok());
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let amountToken2 = dex.getEquivalentToken2Estimate(1_000).unwrap();
+            assert_eq!(
+                dex.provideWithMin(1_000, amountToken2, minShares, u64::MAX),
+                Err(Error::SlippageExceeded)
+            );
+
+            let share = dex.getMinSharesForAmounts(1_000, amountToken2).unwrap();
+            assert!(share < minShares);
+            assert_eq!(
+                dex.provideWithMin(1_000, amountToken2, share, u64::MAX),
+                Ok(share)
+            );
+        }
+
+        #[ink::test]
+        fn withdraw_with_min_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(0);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.provide(10_000, 10_000, u64::MAX).is_ok());
+            let (minToken1, minToken2) = dex.getWithdrawEstimate(crate::PRECISION).unwrap();
+
+            // A swap lands between the estimate & the withdraw, shifting the pool ratio
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.swapToken1GivenToken1(2_500, 0, u64::MAX).is_ok());
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(
+                dex.withdrawWithMin(crate::PRECISION, minToken1, minToken2, u64::MAX),
+                Err(Error::SlippageExceeded)
+            );
+
+            // The pool now holds more Token1 & less Token2
+            let (amountToken1, amountToken2) = dex.getWithdrawEstimate(crate::PRECISION).unwrap();
+            assert!(amountToken1 > minToken1 && amountToken2 < minToken2);
+            assert_eq!(
+                dex.withdrawWithMin(crate::PRECISION, minToken1, amountToken2, u64::MAX),
+                Ok((amountToken1, amountToken2))
+            );
+        }
+
+        #[ink::test]
+        fn imbalance_fee_scales_with_price_impact() {
+            let mut dex = Dex::newWithImbalanceFee(3, true, 10, 100);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            assert!(dex.provide(100_000, 100_000, u64::MAX).is_ok());
+
+            // A tiny trade barely moves the ratio & pays the minimum fee
+            assert_eq!(dex.getEffectiveFee(10), 10);
+            // 100_000 / 102_000 rounds down to 9_803 bps, a 1 - 0.9803^2 = 391 bps price impact
+            assert_eq!(dex.getEffectiveFee(2_000), 10 + 90 * 391 / 1_000);
+            // Past VOLATILITY_CAP_BPS of impact the fee is capped
+            assert_eq!(dex.getEffectiveFee(50_000), 100);
+
+            // Swaps are priced & charged at the effective fee
+            let feeBps = dex.getEffectiveFee(50_000);
+            let amountToken2 = dex.getSwapToken1EstimateGivenToken1(50_000).unwrap();
+            assert_eq!(
+                amountToken2,
+                100_000 - 100_000 * 100_000 / (100_000 + 49_500)
+            );
+            assert_eq!(feeBps, 100);
+            assert_eq!(
+                dex.swapToken1GivenToken1(50_000, 0, u64::MAX)
+                    .map(|result| result.amountOut),
+                Ok(amountToken2)
+            );
+
+            // Exact output swaps are charged on the impact of their output
+            let amountToken1 = dex.getSwapToken2EstimateGivenToken1(5_000).unwrap();
+            assert_eq!(
+                dex.swapToken2GivenToken1(5_000, amountToken1, u64::MAX)
+                    .map(|result| result.amountIn),
+                Ok(amountToken1)
+            );
+
+            // With the flag off the static fee applies whatever the trade size
+            let dex = Dex::newWithImbalanceFee(3, false, 10, 100);
+            assert_eq!(dex.getEffectiveFee(50_000), 30);
+            let dex = Dex::newWithImbalanceFee(3, true, 100, 10);
+            assert_eq!(dex.getEffectiveFee(50_000), 30);
+        }
+
+        #[ink::test]
+        fn flash_swap_checks() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(
+                dex.flashSwap(10, 0, accounts.django),
+                Err(Error::ZeroLiquidity)
+            );
+            dex.faucet(10_000, 10_000).unwrap();
+            assert!(dex.provide(1_000, 1_000, u64::MAX).is_ok());
+
+            assert_eq!(dex.flashSwap(0, 0, accounts.django), Err(Error::ZeroAmount));
+            assert_eq!(
+                dex.flashSwap(1_000, 0, accounts.django),
+                Err(Error::InsufficientLiquidity)
+            );
+            assert_eq!(
+                dex.flashSwap(0, 1_000, accounts.django),
+                Err(Error::InsufficientLiquidity)
+            );
+
+            let mut weighted = Dex::newWeighted(3, 80);
+            assert_eq!(weighted.setFaucetEnabled(true), Ok(()));
+            weighted.faucet(10_000, 10_000).unwrap();
+            assert!(weighted.provide(1_000, 1_000, u64::MAX).is_ok());
+            assert_eq!(
+                weighted.flashSwap(10, 0, accounts.django),
+                Err(Error::UnsupportedPool)
+            );
+        }
+
+        #[ink::test]
+        fn withdraw_all_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            assert_eq!(dex.withdrawAll(u64::MAX), Err(Error::ZeroLiquidity));
+            let share = dex.provide(1_000, 1_000, u64::MAX).unwrap();
+            assert!(dex.swapToken1GivenToken1(500, 0, u64::MAX).is_ok());
+
+            let estimate = dex.getWithdrawEstimate(share).unwrap();
+            assert_eq!(dex.withdrawAll(u64::MAX), Ok(estimate));
+            assert_eq!(dex.getMyHoldings().2, 0);
+            assert_eq!(dex.withdrawAll(u64::MAX), Err(Error::ZeroLiquidity));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.withdrawAll(u64::MAX), Err(Error::ZeroLiquidity));
+        }
+
+        #[ink::test]
+        fn faucet_is_disabled_by_default() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert!(!dex.getFaucetEnabled());
+            assert_eq!(dex.faucet(100, 100), Err(Error::FaucetDisabled));
+            assert_eq!(dex.getMyHoldings(), (0, 0, 0));
+
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            assert_eq!(dex.faucet(100, 100), Ok(()));
+            assert_eq!(dex.getMyHoldings(), (100, 100, 0));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.setFaucetEnabled(false), Err(Error::NotOwner));
+
+            // Token contracts cannot be minted by the pool
+            let mut dex = Dex::newWithTokens(3, accounts.django, accounts.frank);
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(dex.faucet(100, 100), Err(Error::FaucetDisabled));
+        }
+
+        #[ink::test]
+        fn reentrancy_guard_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            let share = dex.provide(1_000, 1_000, u64::MAX).unwrap();
+            let details = dex.getPoolDetails();
+
+            // Lock held by an outer call, as seen by a reentering flash swap callback
+            dex.locked.set(&true);
+            assert_eq!(dex.provide(100, 100, u64::MAX), Err(Error::Reentrancy));
+            assert_eq!(dex.withdraw(share / 2, u64::MAX), Err(Error::Reentrancy));
+            assert_eq!(dex.withdrawAll(u64::MAX), Err(Error::Reentrancy));
+            assert_eq!(
+                dex.swapToken1GivenToken1(100, 0, u64::MAX),
+                Err(Error::Reentrancy)
+            );
+            assert_eq!(
+                dex.swapToken1GivenToken2(10, 100, u64::MAX),
+                Err(Error::Reentrancy)
+            );
+            assert_eq!(
+                dex.swapToken2GivenToken2(100, 0, u64::MAX),
+                Err(Error::Reentrancy)
+            );
+            assert_eq!(
+                dex.swapToken2GivenToken1(10, 100, u64::MAX),
+                Err(Error::Reentrancy)
+            );
+            assert_eq!(
+                dex.swapTo(true, 100, 0, accounts.bob, u64::MAX),
+                Err(Error::Reentrancy)
+            );
+            assert_eq!(
+                dex.flashSwap(10, 0, accounts.django),
+                Err(Error::Reentrancy)
+            );
+            assert_eq!(dex.getPoolDetails(), details);
+
+            // The lock is released after failing & succeeding calls alike
+            dex.locked.set(&false);
+            assert_eq!(
+                dex.swapToken1GivenToken1(1_000, 1_000, u64::MAX),
+                Err(Error::SlippageExceeded)
+            );
+            assert_eq!(dex.locked.get(), Some(false));
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            assert_eq!(dex.locked.get(), Some(false));
+            assert!(dex.withdraw(share / 2, u64::MAX).is_ok());
+            assert_eq!(dex.locked.get(), Some(false));
+        }
+
+        #[ink::test]
+        fn pause_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            let share = dex.provide(1_000, 1_000, u64::MAX).unwrap();
+            assert!(!dex.isPaused());
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.pause(), Err(Error::NotOwner));
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+
+            let events = ink::env::test::recorded_events().count();
+            assert_eq!(dex.pause(), Ok(()));
+            assert_eq!(ink::env::test::recorded_events().count(), events + 1);
+            assert!(dex.isPaused());
+            assert!(dex.stats().paused);
+            assert_eq!(dex.provide(100, 100, u64::MAX), Err(Error::Paused));
+            assert_eq!(dex.withdraw(share, u64::MAX), Err(Error::Paused));
+            assert_eq!(
+                dex.swapToken1GivenToken1(100, 0, u64::MAX),
+                Err(Error::Paused)
+            );
+            assert_eq!(
+                dex.swapToken1GivenToken2(10, 100, u64::MAX),
+                Err(Error::Paused)
+            );
+            assert_eq!(
+                dex.swapToken2GivenToken2(100, 0, u64::MAX),
+                Err(Error::Paused)
+            );
+            assert_eq!(
+                dex.swapToken2GivenToken1(10, 100, u64::MAX),
+                Err(Error::Paused)
+            );
+            assert_eq!(dex.flashSwap(10, 0, accounts.django), Err(Error::Paused));
+
+            // Read-only getters stay available
+            assert_eq!(dex.getPoolDetails().0, 1_000);
+            assert!(dex.getSpotPriceToken1().is_ok());
+            assert!(dex.getSwapToken1EstimateGivenToken1(100).is_ok());
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.unpause(), Err(Error::NotOwner));
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(dex.unpause(), Ok(()));
+            assert_eq!(ink::env::test::recorded_events().count(), events + 2);
+            assert!(!dex.isPaused());
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            assert!(dex.withdraw(share, u64::MAX).is_ok());
+        }
+
+        #[ink::test]
+        fn share_transfers_work() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(10_000, 10_000).unwrap();
+            let share = dex.provide(1_000, 1_000, u64::MAX).unwrap();
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            let fees = dex.getClaimableFees();
+            assert_eq!(dex.shareBalanceOf(accounts.alice), share);
+            assert_eq!(dex.getProviderLimit().0, 1);
+
+            assert_eq!(dex.transferShares(accounts.bob, 0), Err(Error::ZeroAmount));
+            assert_eq!(
+                dex.transferShares(accounts.bob, share + 1),
+                Err(Error::InsufficientAmount)
+            );
+            let events = ink::env::test::recorded_events().count();
+            assert_eq!(dex.transferShares(accounts.bob, share / 2), Ok(()));
+            assert_eq!(ink::env::test::recorded_events().count(), events + 1);
+            assert_eq!(dex.shareBalanceOf(accounts.alice), share - share / 2);
+            assert_eq!(dex.shareBalanceOf(accounts.bob), share / 2);
+            assert_eq!(dex.getProviderLimit().0, 2);
+
+            // Bob withdraws the received shares & the fees they accrued move along
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dex.getClaimableFees(), (fees.0 / 2, fees.1 / 2));
+            let estimate = dex.getWithdrawEstimate(share / 2).unwrap();
+            assert_eq!(dex.withdraw(share / 2, u64::MAX), Ok(estimate));
+            assert_eq!(dex.getMyHoldings(), (estimate.0, estimate.1, 0));
+
+            // Allowances
+            assert_eq!(
+                dex.transferSharesFrom(accounts.alice, accounts.charlie, 10),
+                Err(Error::InsufficientAllowance)
+            );
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(dex.approveShares(accounts.bob, 100), Ok(()));
+            assert_eq!(dex.shareAllowance(accounts.alice, accounts.bob), 100);
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                dex.transferSharesFrom(accounts.alice, accounts.charlie, 101),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(
+                dex.transferSharesFrom(accounts.alice, accounts.charlie, 60),
+                Ok(())
+            );
+            assert_eq!(dex.shareAllowance(accounts.alice, accounts.bob), 40);
+            assert_eq!(dex.shareBalanceOf(accounts.charlie), 60);
+
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert!(dex.withdraw(60, u64::MAX).is_ok());
+            assert_eq!(dex.shareBalanceOf(accounts.charlie), 0);
+        }
+
+        #[ink::test]
+        fn swap_result_works() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(100_000, 100_000).unwrap();
+            assert!(dex.provide(10_000, 10_000, u64::MAX).is_ok());
+
+            let estimate = dex.getSwapToken1EstimateGivenToken1(1_000).unwrap();
+            let result = dex.swapToken1GivenToken1(1_000, 0, u64::MAX).unwrap();
+            assert_eq!(result.amountIn, 1_000);
+            assert_eq!(result.amountOut, estimate);
+            assert_eq!(result.feePaid, 1_000 * 3 / 1000);
+            // Spot price moves from 1 to (10_000 - estimate) / 11_000
+            let (totalToken1, totalToken2, _, _) = dex.getPoolDetails();
+            let spotAfter = totalToken2 * crate::PRECISION / totalToken1;
+            assert_eq!(
+                result.priceImpactBps,
+                (crate::PRECISION - spotAfter) * crate::BPS / crate::PRECISION
+            );
+            assert!(result.priceImpactBps > 1_000);
+
+            // Exact output swaps report the input they required
+            let required = dex.getSwapToken2EstimateGivenToken1(500).unwrap();
+            let result = dex.swapToken2GivenToken1(500, required, u64::MAX).unwrap();
+            assert_eq!((result.amountIn, result.amountOut), (required, 500));
+            assert_eq!(result.feePaid, required * 3 / 1000);
+            assert!(result.priceImpactBps > 0);
+        }
+
+        #[ink::test]
+        fn get_amount_out_and_in_work() {
+            let dex = Dex::new(3);
+            // Uniswap V2 with its 0.3% fee: 1_000 * 997 * 2_000_000 / (1_000_000 * 1_000 + 1_000 * 997)
+            assert_eq!(dex.getAmountOut(1_000, 1_000_000, 2_000_000), Ok(1_992));
+            assert_eq!(dex.getAmountIn(1_992, 1_000_000, 2_000_000), Ok(1_000));
+            assert_eq!(dex.getAmountOut(0, 1_000, 1_000), Err(Error::ZeroAmount));
+            assert_eq!(dex.getAmountOut(10, 0, 1_000), Err(Error::ZeroLiquidity));
+            assert_eq!(
+                dex.getAmountIn(1_000, 1_000, 1_000),
+                Err(Error::InsufficientLiquidity)
+            );
+
+            for (amountIn, reserveIn, reserveOut) in [
+                (1_000, 1_000_000, 1_000_000),
+                (123_456, 5_000_000, 7_000_000),
+                (10, 3_000, 1_000),
+                (1_000_000_000, 1_000_000, 1_000_000),
+            ] {
+                let amountOut = dex.getAmountOut(amountIn, reserveIn, reserveOut).unwrap();
+                let required = dex.getAmountIn(amountOut, reserveIn, reserveOut).unwrap();
+                // Rounding the output down can only make the required input smaller
+                assert!(required <= amountIn);
+                assert_eq!(
+                    dex.getAmountOut(required, reserveIn, reserveOut),
+                    Ok(amountOut)
+                );
+            }
+            let amountOut = dex.getAmountOut(1_000, 1_000_000, 1_000_000).unwrap();
+            let required = dex.getAmountIn(amountOut, 1_000_000, 1_000_000).unwrap();
+            assert!(1_000 - required <= 1);
+
+            let weighted = Dex::newWeighted(3, 80);
+            assert_eq!(
+                weighted.getAmountOut(1_000, 1_000_000, 1_000_000),
+                Err(Error::UnsupportedPool)
+            );
+        }
+
+        #[ink::test]
+        fn dust_swaps_are_rejected() {
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            assert!(dex.provide(100_000, 100_000, u64::MAX).is_ok());
+            let holdings = dex.getMyHoldings();
+
+            // A 0.3% fee rounds to zero below 334 units
+            assert_eq!(
+                dex.swapToken1GivenToken1(1, 0, u64::MAX),
+                Err(Error::AmountTooSmall)
+            );
+            assert_eq!(
+                dex.swapToken2GivenToken2(1, 0, u64::MAX),
+                Err(Error::AmountTooSmall)
+            );
+            assert_eq!(
+                dex.swapToken1GivenToken2(1, 10, u64::MAX),
+                Err(Error::AmountTooSmall)
+            );
+            assert_eq!(
+                dex.swapToken2GivenToken1(1, 10, u64::MAX),
+                Err(Error::AmountTooSmall)
+            );
+            assert_eq!(
+                dex.swapToken1GivenToken1(333, 0, u64::MAX),
+                Err(Error::AmountTooSmall)
+            );
+            assert_eq!(dex.getMyHoldings(), holdings);
+            assert!(dex.swapToken1GivenToken1(334, 0, u64::MAX).is_ok());
+
+            // Fee free pools have no fee to round away
+            let mut dex = Dex::new(0);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            assert!(dex.provide(100_000, 100_000, u64::MAX).is_ok());
+            assert!(dex.swapToken1GivenToken1(1, 0, u64::MAX).is_ok());
+
+            let mut dex = Dex::newWithMinSwap(3, 1_000, 2_000);
+            assert_eq!(dex.getMinSwapAmounts(), (1_000, 2_000));
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(1_000_000, 1_000_000).unwrap();
+            assert!(dex.provide(100_000, 100_000, u64::MAX).is_ok());
+            assert_eq!(
+                dex.swapToken1GivenToken1(999, 0, u64::MAX),
+                Err(Error::AmountTooSmall)
+            );
+            assert!(dex.swapToken1GivenToken1(1_000, 0, u64::MAX).is_ok());
+            assert_eq!(
+                dex.swapToken2GivenToken2(1_999, 0, u64::MAX),
+                Err(Error::AmountTooSmall)
+            );
+            assert!(dex.swapToken2GivenToken2(2_000, 0, u64::MAX).is_ok());
+        }
+
+        #[ink::test]
+        fn deadline_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut dex = Dex::new(3);
+            assert_eq!(dex.setFaucetEnabled(true), Ok(()));
+            dex.faucet(100_000, 100_000).unwrap();
+            ink::env::test::set_block_timestamp::<Environment>(1_000);
+
+            // Executing exactly at the deadline is allowed
+            let share = dex.provide(10_000, 10_000, 1_000).unwrap();
+            assert!(dex.swapToken1GivenToken1(1_000, 0, 1_000).is_ok());
+
+            ink::env::test::set_block_timestamp::<Environment>(1_001);
+            let holdings = dex.getMyHoldings();
+            assert_eq!(
+                dex.provide(1_000, 1_000, 1_000),
+                Err(Error::DeadlineExpired)
+            );
+            assert_eq!(
+                dex.provideWithMin(1_000, 1_000, 0, 1_000),
+                Err(Error::DeadlineExpired)
+            );
+            assert_eq!(dex.withdraw(share, 1_000), Err(Error::DeadlineExpired));
+            assert_eq!(
+                dex.withdrawWithMin(share, 0, 0, 1_000),
+                Err(Error::DeadlineExpired)
+            );
+            assert_eq!(dex.withdrawAll(1_000), Err(Error::DeadlineExpired));
+            assert_eq!(
+                dex.swapToken1GivenToken1(1_000, 0, 1_000),
+                Err(Error::DeadlineExpired)
+            );
+            assert_eq!(
+                dex.swapToken1GivenToken2(1_000, 10_000, 1_000),
+                Err(Error::DeadlineExpired)
+            );
+            assert_eq!(
+                dex.swapToken2GivenToken2(1_000, 0, 1_000),
+                Err(Error::DeadlineExpired)
+            );
+            assert_eq!(
+                dex.swapToken2GivenToken1(1_000, 10_000, 1_000),
+                Err(Error::DeadlineExpired)
+            );
+            assert_eq!(
+                dex.swapTo(true, 1_000, 0, accounts.bob, 1_000),
+                Err(Error::DeadlineExpired)
+            );
+            assert_eq!(
+                dex.batchSwap(
+                    vec![SwapOrder {
+                        token1In: true,
+                        amountIn: 1_000,
+                        minOut: 0,
+                    }],
+                    1_000
+                ),
+                Err(Error::DeadlineExpired)
+            );
+            assert_eq!(dex.getMyHoldings(), holdings);
+
+            assert!(dex.swapToken2GivenToken2(1_000, 0, 2_000).is_ok());
+            assert!(dex.withdraw(share, 2_000).is_ok());
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use carbon_token::CarbonTokenRef;
+        use flash_borrower::FlashBorrowerRef;
+        use ink_e2e::build_message;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        #[ink_e2e::test(additional_contracts = "../carbon_token/Cargo.toml")]
+        async fn combined_position_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token = client
+                .instantiate(
+                    "carbon_token",
+                    &ink_e2e::alice(),
+                    CarbonTokenRef::new(0),
+                    0,
+                    None,
+                )
+                .await
+                .expect("carbon_token instantiate failed")
+                .account_id;
+            let dex = client
+                .instantiate("dex", &ink_e2e::alice(), DexRef::new(3), 0, None)
+                .await
+                .expect("dex instantiate failed")
+                .account_id;
+
+            let mint = build_message::<CarbonTokenRef>(token.clone()).call(|token| token.mint(500));
+            client
+                .call(&ink_e2e::alice(), mint, 0, None)
+                .await
+                .expect("mint failed");
+
+            let setToken =
+                build_message::<DexRef>(dex.clone()).call(|dex| dex.setCarbonToken(token));
+            client
+                .call(&ink_e2e::alice(), setToken, 0, None)
+                .await
+                .expect("setCarbonToken failed");
+
+            let enableFaucet =
+                build_message::<DexRef>(dex.clone()).call(|dex| dex.setFaucetEnabled(true));
+            client
+                .call(&ink_e2e::alice(), enableFaucet, 0, None)
+                .await
+                .expect("setFaucetEnabled failed");
+            let faucet = build_message::<DexRef>(dex.clone()).call(|dex| dex.faucet(1_000, 2_000));
+            client
+                .call(&ink_e2e::alice(), faucet, 0, None)
+                .await
+                .expect("faucet failed");
+
+            let provide =
+                build_message::<DexRef>(dex.clone()).call(|dex| dex.provide(100, 200, u64::MAX));
+            client
+                .call(&ink_e2e::alice(), provide, 0, None)
+                .await
+                .expect("provide failed");
+
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let position =
+                build_message::<DexRef>(dex.clone()).call(|dex| dex.getCombinedPosition(alice));
+            let position = client
+                .call_dry_run(&ink_e2e::alice(), &position, 0, None)
+                .await
+                .return_value();
+            assert_eq!(
+                position,
+                Ok((
+                    500,
+                    900,
+                    1_800,
+                    100 * crate::PRECISION - crate::MINIMUM_LIQUIDITY
+                ))
+            );
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../carbon_token/Cargo.toml")]
+        async fn buyback_and_retire_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            // Token1 is the CarbonToken whose reserve is bought back & retired
+            let mut tokens = Vec::new();
+            for _ in 0..2 {
+                let token = client
+                    .instantiate(
+                        "carbon_token",
+                        &ink_e2e::alice(),
+                        CarbonTokenRef::new(10_000),
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("carbon_token instantiate failed")
+                    .account_id;
+                tokens.push(token);
+            }
+            let token = tokens[0];
+            let dex = client
+                .instantiate(
+                    "dex",
+                    &ink_e2e::alice(),
+                    DexRef::newWithTokens(10, tokens[0], tokens[1]),
+                    0,
+                    None,
+                )
+                .await
+                .expect("dex instantiate failed")
+                .account_id;
+
+            for token in tokens.iter() {
+                let approve = build_message::<CarbonTokenRef>(token.clone())
+                    .call(|token| token.approve(dex, 5_000));
+                client
+                    .call(&ink_e2e::alice(), approve, 0, None)
+                    .await
+                    .expect("approve failed");
+            }
+
+            let setToken =
+                build_message::<DexRef>(dex.clone()).call(|dex| dex.setCarbonToken(token));
+            client
+                .call(&ink_e2e::alice(), setToken, 0, None)
+                .await
+                .expect("setCarbonToken failed");
+            let setFee =
+                build_message::<DexRef>(dex.clone()).call(|dex| dex.setProtocolFeeBps(crate::BPS));
+            client
+                .call(&ink_e2e::alice(), setFee, 0, None)
+                .await
+                .expect("setProtocolFeeBps failed");
+
+            let provide = build_message::<DexRef>(dex.clone())
+                .call(|dex| dex.provide(1_000, 1_000, u64::MAX));
+            client
+                .call(&ink_e2e::alice(), provide, 0, None)
+                .await
+                .expect("provide failed");
+            let swap = build_message::<DexRef>(dex.clone())
+                .call(|dex| dex.swapToken2GivenToken2(1_000, 0, u64::MAX));
+            client
+                .call(&ink_e2e::alice(), swap, 0, None)
+                .await
+                .expect("swap failed");
+
+            let buyback = build_message::<DexRef>(dex.clone()).call(|dex| dex.buybackAndRetire(1));
+            let retired = client
+                .call(&ink_e2e::alice(), buyback, 0, None)
+                .await
+                .expect("buybackAndRetire failed")
+                .return_value()
+                .expect("buybackAndRetire reverted");
+            assert!(retired > 0);
+
+            let totalRetired =
+                build_message::<CarbonTokenRef>(token.clone()).call(|token| token.total_retired());
+            let totalRetired = client
+                .call_dry_run(&ink_e2e::alice(), &totalRetired, 0, None)
+                .await
+                .return_value();
+            assert_eq!(totalRetired, retired);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../carbon_token/Cargo.toml")]
+        async fn token_contracts_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let mut tokens = Vec::new();
+            for _ in 0..2 {
+                let token = client
+                    .instantiate(
+                        "carbon_token",
+                        &ink_e2e::alice(),
+                        CarbonTokenRef::new(10_000),
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("carbon_token instantiate failed")
+                    .account_id;
+                tokens.push(token);
+            }
+            let dex = client
+                .instantiate(
+                    "dex",
+                    &ink_e2e::alice(),
+                    DexRef::newWithTokens(3, tokens[0], tokens[1]),
+                    0,
+                    None,
+                )
+                .await
+                .expect("dex instantiate failed")
+                .account_id;
+
+            for token in tokens.iter() {
+                let approve = build_message::<CarbonTokenRef>(token.clone())
+                    .call(|token| token.approve(dex, 10_000));
+                client
+                    .call(&ink_e2e::alice(), approve, 0, None)
+                    .await
+                    .expect("approve failed");
+            }
+
+            let provide = build_message::<DexRef>(dex.clone())
+                .call(|dex| dex.provide(1_000, 2_000, u64::MAX));
+            client
+                .call(&ink_e2e::alice(), provide, 0, None)
+                .await
+                .expect("provide failed");
+            let swap = build_message::<DexRef>(dex.clone())
+                .call(|dex| dex.swapToken1GivenToken1(1_000, 0, u64::MAX));
+            let amountOut = client
+                .call(&ink_e2e::alice(), swap, 0, None)
+                .await
+                .expect("swap failed")
+                .return_value()
+                .expect("swap reverted")
+                .amountOut;
+
+            let holdings = build_message::<DexRef>(dex.clone()).call(|dex| dex.getMyHoldings());
+            let holdings = client
+                .call_dry_run(&ink_e2e::alice(), &holdings, 0, None)
+                .await
+                .return_value();
+            assert_eq!(
+                holdings,
+                (
+                    8_000,
+                    8_000 + amountOut,
+                    100 * crate::PRECISION - crate::MINIMUM_LIQUIDITY
+                )
+            );
+
+            let poolBalance = build_message::<CarbonTokenRef>(tokens[1].clone())
+                .call(|token| token.balance_of(dex));
+            let poolBalance = client
+                .call_dry_run(&ink_e2e::alice(), &poolBalance, 0, None)
+                .await
+                .return_value();
+            assert_eq!(poolBalance, 2_000 - amountOut);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../carbon_token/Cargo.toml")]
+        async fn taxed_token_transfer_rejected(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let mut tokens = Vec::new();
+            for _ in 0..2 {
+                let token = client
+                    .instantiate(
+                        "carbon_token",
+                        &ink_e2e::alice(),
+                        CarbonTokenRef::new(10_000),
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("carbon_token instantiate failed")
+                    .account_id;
+                tokens.push(token);
+            }
+            let dex = client
+                .instantiate(
+                    "dex",
+                    &ink_e2e::alice(),
+                    DexRef::newWithTokens(3, tokens[0], tokens[1]),
+                    0,
+                    None,
+                )
+                .await
+                .expect("dex instantiate failed")
+                .account_id;
+
+            for token in tokens.iter() {
+                let approve = build_message::<CarbonTokenRef>(token.clone())
+                    .call(|token| token.approve(dex, 10_000));
+                client
+                    .call(&ink_e2e::alice(), approve, 0, None)
+                    .await
+                    .expect("approve failed");
+            }
+
+            // A 1% transfer tax credits the pool less than the provided amount
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let setTax = build_message::<CarbonTokenRef>(tokens[0].clone())
+                .call(|token| token.set_transfer_tax(100, bob));
+            client
+                .call(&ink_e2e::alice(), setTax, 0, None)
+                .await
+                .expect("set_transfer_tax failed");
+            let provide = build_message::<DexRef>(dex.clone())
+                .call(|dex| dex.provide(1_000, 2_000, u64::MAX));
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &provide, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Err(Error::TokenTransferShortfall));
+
+            // Exempting the pool from the tax lets it receive the full amount
+            let setExempt = build_message::<CarbonTokenRef>(tokens[0].clone())
+                .call(|token| token.set_tax_exempt(dex, true));
+            client
+                .call(&ink_e2e::alice(), setExempt, 0, None)
+                .await
+                .expect("set_tax_exempt failed");
+            client
+                .call(&ink_e2e::alice(), provide, 0, None)
+                .await
+                .expect("provide failed");
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(
+            additional_contracts = "../carbon_token/Cargo.toml flash_borrower/Cargo.toml"
+        )]
+        async fn flash_swap_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let mut tokens = Vec::new();
+            for _ in 0..2 {
+                let token = client
+                    .instantiate(
+                        "carbon_token",
+                        &ink_e2e::alice(),
+                        CarbonTokenRef::new(10_000),
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("carbon_token instantiate failed")
+                    .account_id;
+                tokens.push(token);
+            }
+            let dex = client
+                .instantiate(
+                    "dex",
+                    &ink_e2e::alice(),
+                    DexRef::newWithTokens(3, tokens[0], tokens[1]),
+                    0,
+                    None,
+                )
+                .await
+                .expect("dex instantiate failed")
+                .account_id;
+
+            // One borrower repays 1% over the borrowed amount, which covers the 0.3% fee, the other only the principal
+            let mut borrowers = Vec::new();
+            for repayBps in [10_100, 10_000] {
+                let borrower = client
+                    .instantiate(
+                        "flash_borrower",
+                        &ink_e2e::alice(),
+                        FlashBorrowerRef::new(tokens[0], tokens[1], repayBps),
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("flash_borrower instantiate failed")
+                    .account_id;
+                borrowers.push(borrower);
+            }
+
+            for token in tokens.iter() {
+                let approve = build_message::<CarbonTokenRef>(token.clone())
+                    .call(|token| token.approve(dex, 10_000));
+                client
+                    .call(&ink_e2e::alice(), approve, 0, None)
+                    .await
+                    .expect("approve failed");
+                for borrower in borrowers.iter() {
+                    let transfer = build_message::<CarbonTokenRef>(token.clone())
+                        .call(|token| token.transfer(*borrower, 100));
+                    client
+                        .call(&ink_e2e::alice(), transfer, 0, None)
+                        .await
+                        .expect("transfer failed");
+                }
+            }
+
+            let provide = build_message::<DexRef>(dex.clone())
+                .call(|dex| dex.provide(1_000, 1_000, u64::MAX));
+            client
+                .call(&ink_e2e::alice(), provide, 0, None)
+                .await
+                .expect("provide failed");
+
+            let flashSwap = build_message::<DexRef>(dex.clone())
+                .call(|dex| dex.flashSwap(100, 0, borrowers[0]));
+            client
+                .call(&ink_e2e::alice(), flashSwap, 0, None)
+                .await
+                .expect("flash swap failed");
+            let details = build_message::<DexRef>(dex.clone()).call(|dex| dex.getPoolDetails());
+            let (totalToken1, totalToken2, _, _) = client
+                .call_dry_run(&ink_e2e::alice(), &details, 0, None)
+                .await
+                .return_value();
+            assert_eq!((totalToken1, totalToken2), (1_001, 1_000));
+
+            let flashSwap = build_message::<DexRef>(dex.clone())
+                .call(|dex| dex.flashSwap(100, 0, borrowers[1]));
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &flashSwap, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Err(Error::InsufficientLiquidity));
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(
+            additional_contracts = "../carbon_token/Cargo.toml flash_borrower/Cargo.toml"
+        )]
+        async fn flash_swap_reentrancy_rejected(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let mut tokens = Vec::new();
+            for _ in 0..2 {
+                let token = client
+                    .instantiate(
+                        "carbon_token",
+                        &ink_e2e::alice(),
+                        CarbonTokenRef::new(10_000),
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("carbon_token instantiate failed")
+                    .account_id;
+                tokens.push(token);
+            }
+            let dex = client
+                .instantiate(
+                    "dex",
+                    &ink_e2e::alice(),
+                    DexRef::newWithTokens(3, tokens[0], tokens[1]),
+                    0,
+                    None,
+                )
+                .await
+                .expect("dex instantiate failed")
+                .account_id;
+            let borrower = client
+                .instantiate(
+                    "flash_borrower",
+                    &ink_e2e::alice(),
+                    FlashBorrowerRef::new_reentrant(tokens[0], tokens[1], 10_100),
+                    0,
+                    None,
+                )
+                .await
+                .expect("flash_borrower instantiate failed")
+                .account_id;
+
+            for token in tokens.iter() {
+                let approve = build_message::<CarbonTokenRef>(token.clone())
+                    .call(|token| token.approve(dex, 10_000));
+                client
+                    .call(&ink_e2e::alice(), approve, 0, None)
+                    .await
+                    .expect("approve failed");
+                let transfer = build_message::<CarbonTokenRef>(token.clone())
+                    .call(|token| token.transfer(borrower, 100));
+                client
+                    .call(&ink_e2e::alice(), transfer, 0, None)
+                    .await
+                    .expect("transfer failed");
+            }
+
+            let provide = build_message::<DexRef>(dex.clone())
+                .call(|dex| dex.provide(1_000, 1_000, u64::MAX));
+            client
+                .call(&ink_e2e::alice(), provide, 0, None)
+                .await
+                .expect("provide failed");
+
+            // The handler's withdraw is rejected while the flash swap holds the lock
+            let flashSwap =
+                build_message::<DexRef>(dex.clone()).call(|dex| dex.flashSwap(100, 0, borrower));
+            client
+                .call(&ink_e2e::alice(), flashSwap, 0, None)
+                .await
+                .expect("flash swap failed");
+            let reentryResult = build_message::<FlashBorrowerRef>(borrower.clone())
+                .call(|borrower| borrower.reentry_result());
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &reentryResult, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Some(Err(Error::Reentrancy as u8)));
+
+            // The lock was released, so the provider can withdraw afterwards
+            let withdraw =
+                build_message::<DexRef>(dex.clone()).call(|dex| dex.withdraw(100, u64::MAX));
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &withdraw, 0, None)
+                .await
+                .return_value();
+            assert!(result.is_ok());
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../carbon_token/Cargo.toml")]
+        async fn swap_via_bridge_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+
+            // Token A & Token C only trade through the bridge token
+            let mut tokens = Vec::new();
+            for _ in 0..3 {
+                let token = client
+                    .instantiate(
+                        "carbon_token",
+                        &ink_e2e::alice(),
+                        CarbonTokenRef::new(10_000),
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("carbon_token instantiate failed")
+                    .account_id;
+                tokens.push(token);
+            }
+            let (tokenA, bridge, tokenC) = (tokens[0], tokens[1], tokens[2]);
+
+            let mut pools = Vec::new();
+            for (token1, token2) in [(tokenA, bridge), (tokenC, bridge), (tokenA, tokenC)] {
+                let pool = client
+                    .instantiate(
+                        "dex",
+                        &ink_e2e::alice(),
+                        DexRef::newWithTokens(3, token1, token2),
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("dex instantiate failed")
+                    .account_id;
+                pools.push(pool);
+            }
+            let (poolA, poolB, router) = (pools[0], pools[1], pools[2]);
+
+            for (token, spender) in [
+                (tokenA, poolA),
+                (bridge, poolA),
+                (bridge, poolB),
+                (tokenC, poolB),
+                (tokenA, router),
+            ] {
+                let approve = build_message::<CarbonTokenRef>(token.clone())
+                    .call(|token| token.approve(spender, 5_000));
+                client
+                    .call(&ink_e2e::alice(), approve, 0, None)
+                    .await
+                    .expect("approve failed");
+            }
+            for pool in [poolA, poolB] {
+                let provide = build_message::<DexRef>(pool.clone())
+                    .call(|dex| dex.provide(2_000, 2_000, u64::MAX));
+                client
+                    .call(&ink_e2e::alice(), provide, 0, None)
+                    .await
+                    .expect("provide failed");
+            }
+
+            // Pools without a common Token2 are rejected
+            let swap = build_message::<DexRef>(router.clone()).call(|dex| {
+                dex.swapExactToken1ForToken1ViaBridge(poolA, router, 1_000, 0, u64::MAX)
+            });
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &swap, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Err(Error::InvalidRoute));
+
+            // A failing second leg fails the whole swap
+            let swap = build_message::<DexRef>(router.clone()).call(|dex| {
+                dex.swapExactToken1ForToken1ViaBridge(poolA, poolB, 1_000, 1_000, u64::MAX)
+            });
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &swap, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Err(Error::SlippageExceeded));
+
+            let swap = build_message::<DexRef>(router.clone()).call(|dex| {
+                dex.swapExactToken1ForToken1ViaBridge(poolA, poolB, 1_000, 1, u64::MAX)
+            });
+            let amountOut = client
+                .call(&ink_e2e::alice(), swap, 0, None)
+                .await
+                .expect("swap failed")
+                .return_value()
+                .expect("swap reverted");
+            assert!(amountOut > 0);
+
+            let balance = build_message::<CarbonTokenRef>(tokenC.clone())
+                .call(|token| token.balance_of(alice));
+            let balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 8_000 + amountOut);
+
+            // The router keeps none of the bridge token
+            let balance = build_message::<CarbonTokenRef>(bridge.clone())
+                .call(|token| token.balance_of(router));
+            let balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 0);
+
+            Ok(())
+        }
     }
 }